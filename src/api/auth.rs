@@ -0,0 +1,531 @@
+use crate::api::client::{ApiError, ApiUrl};
+use crate::api::contributions::ContributionKind;
+use crate::api::issues::Issues;
+use crate::api::pull_request_reviews::PullRequestReviews;
+use crate::api::pull_requests::PullRequests;
+use crate::api::recording::RawResponse;
+use crate::api::repositories::Repositories;
+use crate::api::{comments, commits, discussions};
+use crate::utils::{self, DateTime};
+use reqwest::header::HeaderMap;
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use time::Duration;
+
+/// Installation tokens are refreshed once they have less than this long left to live.
+const REFRESH_BEFORE_EXPIRY: Duration = Duration::minutes(5);
+
+/// The classic token scopes that each of our queries needs in order to see everything.
+/// Without these, GitHub quietly leaves out contributions to private repositories.
+const REQUIRED_SCOPES: &[(&str, &[&str])] = &[
+    (PullRequests::NAME, PullRequests::SCOPES),
+    (PullRequestReviews::NAME, PullRequestReviews::SCOPES),
+    (Issues::NAME, Issues::SCOPES),
+    (Repositories::NAME, Repositories::SCOPES),
+    (commits::NAME, commits::SCOPES),
+    (comments::NAME, comments::SCOPES),
+    (discussions::NAME, discussions::SCOPES),
+];
+
+/// How we authenticate with GitHub.
+pub enum Auth {
+    /// A token for a user, like a personal access token.
+    Token(String),
+    /// Authenticate as an installation of a GitHub App.
+    App(GitHubApp),
+}
+
+/// A GitHub App installation that we authenticate as. Installation tokens only last
+/// for an hour, so we exchange a JWT signed with the app's private key for a new one
+/// whenever the current one is close to expiring.
+pub struct GitHubApp {
+    app_id: String,
+    key: jsonwebtoken::EncodingKey,
+    installation_id: u64,
+    token: tokio::sync::Mutex<Option<InstallationToken>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct InstallationToken {
+    token: String,
+    expires_at: DateTime,
+}
+
+// Don't print any secrets.
+impl fmt::Debug for Auth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Auth::Token(_) => f.write_str("Token(..)"),
+            Auth::App(app) => f
+                .debug_struct("App")
+                .field("app_id", &app.app_id)
+                .field("installation_id", &app.installation_id)
+                .finish_non_exhaustive(),
+        }
+    }
+}
+
+impl Auth {
+    /// Something which identifies who we're authenticating as without giving away any
+    /// secrets, so that responses seen by one token aren't reused for another which can
+    /// see more or less. App installation tokens change every hour, so apps are
+    /// identified by their installation instead.
+    pub fn identity(&self) -> String {
+        match self {
+            Auth::Token(token) => utils::sha256_hex(token.as_bytes()),
+            Auth::App(app) => format!("app {} installation {}", app.app_id, app.installation_id),
+        }
+    }
+
+    /// The token to send with the next request.
+    pub async fn token(&self, client: &Client, url: &ApiUrl) -> Result<String, ApiError> {
+        match self {
+            Auth::Token(token) => Ok(token.clone()),
+            Auth::App(app) => app.token(client, url).await,
+        }
+    }
+}
+
+impl GitHubApp {
+    /// A GitHub App with the given ID and PEM encoded private key, authenticating
+    /// as the given installation of it.
+    pub fn new(
+        app_id: String,
+        private_key_pem: &[u8],
+        installation_id: u64,
+    ) -> Result<GitHubApp, anyhow::Error> {
+        let key = jsonwebtoken::EncodingKey::from_rsa_pem(private_key_pem)
+            .map_err(|e| anyhow::anyhow!("Invalid GitHub App private key: {e}"))?;
+        Ok(GitHubApp {
+            app_id,
+            key,
+            installation_id,
+            token: tokio::sync::Mutex::new(None),
+        })
+    }
+
+    async fn token(&self, client: &Client, url: &ApiUrl) -> Result<String, ApiError> {
+        // Holding the lock while refreshing means that concurrent requests wait for
+        // one new token rather than each asking for their own.
+        let mut token = self.token.lock().await;
+        let now = DateTime::now();
+        match &*token {
+            Some(current) if current.expires_at.0 - now.0 > REFRESH_BEFORE_EXPIRY => {
+                Ok(current.token.clone())
+            }
+            _ => {
+                let new_token = self.fetch_token(client, url, now).await?;
+                let value = new_token.token.clone();
+                *token = Some(new_token);
+                Ok(value)
+            }
+        }
+    }
+
+    /// Exchange a freshly signed JWT for an installation token. These requests are never
+    /// recorded or cached, since they contain secrets.
+    async fn fetch_token(
+        &self,
+        client: &Client,
+        url: &ApiUrl,
+        now: DateTime,
+    ) -> Result<InstallationToken, ApiError> {
+        #[derive(Serialize)]
+        struct Claims<'a> {
+            iat: i64,
+            exp: i64,
+            iss: &'a str,
+        }
+
+        // GitHub recommends backdating the JWT a little in case our clock is ahead of
+        // theirs, and won't accept one which lasts longer than 10 minutes.
+        let now = now.0.unix_timestamp();
+        let claims = Claims {
+            iat: now - 60,
+            exp: now + 9 * 60,
+            iss: &self.app_id,
+        };
+        let header = jsonwebtoken::Header::new(jsonwebtoken::Algorithm::RS256);
+        let jwt = jsonwebtoken::encode(&header, &claims, &self.key)
+            .map_err(|e| ApiError::AppAuth(format!("Failed to sign JWT: {e}")))?;
+
+        let path = format!("app/installations/{}/access_tokens", self.installation_id);
+        let res = client
+            .post(url.rest(&path))
+            .bearer_auth(jwt)
+            .header("Accept", "application/vnd.github+json")
+            .header("X-GitHub-Api-Version", "2022-11-28")
+            .header("User-Agent", "jsdw-github-summarizer")
+            .send()
+            .await?;
+
+        let status = res.status();
+        let body = res.text().await?;
+        if !status.is_success() {
+            return Err(ApiError::AppAuth(format!(
+                "Failed to get a token for installation {} of GitHub App {} ({}: {})",
+                self.installation_id,
+                self.app_id,
+                status.as_u16(),
+                github_message(&body)
+            )));
+        }
+
+        serde_json::from_str(&body).map_err(|e| {
+            ApiError::AppAuth(format!("Failed to decode installation token response: {e}"))
+        })
+    }
+}
+
+/// What GitHub told us about our token when we asked who it belongs to.
+#[derive(Debug, Clone)]
+pub struct TokenCheck {
+    /// The user that the token belongs to, if GitHub would tell us.
+    pub login: Option<String>,
+    pub kind: TokenKind,
+    /// Whether the token needs authorizing for SAML single sign-on.
+    pub sso: Option<SsoStatus>,
+    /// Why GitHub wouldn't tell us who the token belongs to, if it wouldn't.
+    pub problem: Option<String>,
+}
+
+/// The different kinds of token that GitHub hands out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenKind {
+    /// A classic personal access token or OAuth token, with the scopes given.
+    Classic { scopes: Vec<String> },
+    /// A fine-grained personal access token, which has permissions rather than scopes.
+    FineGrained,
+    /// A GitHub App installation token, which doesn't belong to any user.
+    Installation,
+}
+
+/// What the `X-GitHub-SSO` header tells us.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SsoStatus {
+    /// The token must be authorized at this URL before it can be used.
+    Required { url: String },
+    /// Results from these organizations (given by ID) were left out, because the
+    /// token isn't authorized for them.
+    PartialResults { organizations: Vec<String> },
+}
+
+impl TokenCheck {
+    /// Work out what we can from the response to `GET /user`. Fails with some
+    /// guidance if GitHub rejected the token outright.
+    pub fn from_response(token: &str, res: &RawResponse) -> Result<TokenCheck, anyhow::Error> {
+        let sso = SsoStatus::from_headers(&res.headers);
+
+        if res.status == StatusCode::UNAUTHORIZED {
+            return Err(anyhow::anyhow!(
+                "GitHub rejected the token ({}: {}). It may be mistyped, expired or revoked; \
+                check it, or create a new one in your GitHub developer settings.",
+                res.status.as_u16(),
+                github_message(&res.body)
+            ));
+        }
+        if let Some(required @ SsoStatus::Required { .. }) = &sso {
+            return Err(anyhow::anyhow!(required.guidance()));
+        }
+
+        let kind = token_kind(token, &res.headers);
+        let mut check = TokenCheck {
+            login: None,
+            kind,
+            sso,
+            problem: None,
+        };
+
+        if res.status.is_success() {
+            #[derive(Deserialize)]
+            struct UserResponse {
+                login: String,
+            }
+            let user: UserResponse = serde_json::from_str(&res.body)
+                .map_err(|e| anyhow::anyhow!("Failed to decode user response: {e}"))?;
+            check.login = Some(user.login);
+        } else if check.kind != TokenKind::Installation {
+            // Installation tokens aren't allowed to ask who they are, so that's expected.
+            let accepted = res
+                .headers
+                .get("x-accepted-oauth-scopes")
+                .and_then(|v| v.to_str().ok())
+                .map(split_scopes)
+                .unwrap_or_default();
+            let mut problem = format!(
+                "GitHub wouldn't say who the token belongs to ({}: {}).",
+                res.status.as_u16(),
+                github_message(&res.body)
+            );
+            if !accepted.is_empty() {
+                problem.push_str(&format!(
+                    " The token needs one of these scopes: '{}'.",
+                    accepted.join("', '")
+                ));
+            }
+            check.problem = Some(problem);
+        }
+
+        Ok(check)
+    }
+
+    /// Things that the user should know about the token before we fetch anything,
+    /// because they mean that some contributions may be missing.
+    pub fn guidance(&self) -> Vec<String> {
+        let mut notes: Vec<String> = self.problem.iter().cloned().collect();
+
+        match &self.kind {
+            TokenKind::Classic { scopes } => {
+                let missing = missing_scopes(scopes);
+                if !missing.is_empty() {
+                    let (mut scopes, things): (Vec<&str>, Vec<&str>) = missing.into_iter().unzip();
+                    scopes.sort();
+                    scopes.dedup();
+                    notes.push(format!(
+                        "The token doesn't have the '{}' scope, so {} in private repositories \
+                        won't be included. Add the scope to the token to include them.",
+                        scopes.join("', '"),
+                        things.join(", ")
+                    ));
+                }
+            }
+            TokenKind::FineGrained => notes.push(
+                "This is a fine-grained token, so contributions to repositories that it hasn't \
+                been given read access to (including those in other organizations) won't be \
+                included."
+                    .to_owned(),
+            ),
+            TokenKind::Installation => notes.push(
+                "This is a GitHub App installation token, so only contributions to \
+                repositories that the app is installed on will be included."
+                    .to_owned(),
+            ),
+        }
+
+        if let Some(partial @ SsoStatus::PartialResults { .. }) = &self.sso {
+            notes.push(partial.guidance());
+        }
+
+        notes
+    }
+}
+
+impl SsoStatus {
+    /// Read the `X-GitHub-SSO` header from a response, if GitHub sent one. GitHub sends it
+    /// on responses that touch resources of organizations which enforce SAML single sign-on.
+    pub fn from_headers(headers: &HeaderMap) -> Option<SsoStatus> {
+        headers
+            .get("x-github-sso")
+            .and_then(|v| v.to_str().ok())
+            .and_then(SsoStatus::parse)
+    }
+
+    /// What the user needs to do about it.
+    pub fn guidance(&self) -> String {
+        match self {
+            SsoStatus::Required { url } => format!(
+                "This token needs to be authorized for SAML single sign-on before it can be \
+                used. Authorize it here and try again: {url}"
+            ),
+            SsoStatus::PartialResults { organizations } => format!(
+                "The token isn't authorized for SAML single sign-on with {} organization(s) \
+                (IDs: {}), so contributions to their repositories won't be included. \
+                Authorize the token for them in your GitHub developer settings.",
+                organizations.len(),
+                organizations.join(", ")
+            ),
+        }
+    }
+
+    /// Parse an `X-GitHub-SSO` header, which looks like `required; url=https://...`
+    /// or `partial-results; organizations=21955855,20582480`.
+    fn parse(header: &str) -> Option<SsoStatus> {
+        let (status, params) = header.split_once(';').unwrap_or((header, ""));
+        let param = |name: &str| {
+            params.split(';').find_map(|param| {
+                let (key, value) = param.trim().split_once('=')?;
+                (key == name).then(|| value.trim().to_owned())
+            })
+        };
+
+        match status.trim() {
+            "required" => Some(SsoStatus::Required { url: param("url")? }),
+            "partial-results" => Some(SsoStatus::PartialResults {
+                organizations: param("organizations")?
+                    .split(',')
+                    .map(|id| id.trim().to_owned())
+                    .filter(|id| !id.is_empty())
+                    .collect(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Work out the kind of token from its prefix, falling back to the headers GitHub
+/// sent back. Only classic tokens are given an `X-OAuth-Scopes` header.
+fn token_kind(token: &str, headers: &HeaderMap) -> TokenKind {
+    let scopes = headers.get("x-oauth-scopes").and_then(|v| v.to_str().ok());
+    if token.starts_with("github_pat_") {
+        TokenKind::FineGrained
+    } else if token.starts_with("ghs_") {
+        TokenKind::Installation
+    } else if let Some(scopes) = scopes {
+        TokenKind::Classic {
+            scopes: split_scopes(scopes),
+        }
+    } else {
+        TokenKind::FineGrained
+    }
+}
+
+/// The scopes that our queries need but which the token lacks, alongside what
+/// will be missing as a result.
+fn missing_scopes(scopes: &[String]) -> Vec<(&'static str, &'static str)> {
+    let has_scope = |needed: &str| scopes.iter().any(|scope| scope == needed);
+    let mut missing = vec![];
+    for (things, needed) in REQUIRED_SCOPES {
+        for scope in needed.iter().filter(|scope| !has_scope(scope)) {
+            missing.push((*scope, *things));
+        }
+    }
+    missing
+}
+
+/// Scopes are given as a comma separated list like `repo, read:org`.
+fn split_scopes(scopes: &str) -> Vec<String> {
+    scopes
+        .split(',')
+        .map(|scope| scope.trim().to_owned())
+        .filter(|scope| !scope.is_empty())
+        .collect()
+}
+
+/// GitHub's REST errors look like `{ "message": "Bad credentials", ... }`.
+fn github_message(body: &str) -> String {
+    #[derive(Deserialize)]
+    struct ErrorResponse {
+        message: String,
+    }
+    serde_json::from_str::<ErrorResponse>(body)
+        .map(|e| e.message)
+        .unwrap_or_else(|_| body.to_owned())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn response(status: u16, headers: &[(&'static str, &str)], body: &str) -> RawResponse {
+        let mut header_map = HeaderMap::new();
+        for (name, value) in headers {
+            header_map.insert(*name, HeaderValue::from_str(value).unwrap());
+        }
+        RawResponse {
+            status: StatusCode::from_u16(status).unwrap(),
+            headers: header_map,
+            body: body.to_owned(),
+        }
+    }
+
+    #[test]
+    fn parses_sso_headers() {
+        assert_eq!(
+            SsoStatus::parse(
+                "required; url=https://github.com/orgs/acme/sso?authorization_request=abc"
+            ),
+            Some(SsoStatus::Required {
+                url: "https://github.com/orgs/acme/sso?authorization_request=abc".to_owned()
+            })
+        );
+        assert_eq!(
+            SsoStatus::parse("partial-results; organizations=21955855,20582480"),
+            Some(SsoStatus::PartialResults {
+                organizations: vec!["21955855".to_owned(), "20582480".to_owned()]
+            })
+        );
+        assert_eq!(SsoStatus::parse("something-else"), None);
+    }
+
+    #[test]
+    fn notes_missing_scopes() {
+        let res = response(
+            200,
+            &[("x-oauth-scopes", "read:org, gist")],
+            r#"{"login":"octocat"}"#,
+        );
+        let check = TokenCheck::from_response("ghp_abc", &res).unwrap();
+
+        assert_eq!(check.login.as_deref(), Some("octocat"));
+        let notes = check.guidance();
+        assert_eq!(notes.len(), 1);
+        assert!(
+            notes[0].contains("doesn't have the 'repo' scope"),
+            "{}",
+            notes[0]
+        );
+        assert!(
+            notes[0].contains(
+                "pull requests, pull request reviews, issues, repositories, commits, comments, discussions"
+            ),
+            "{}",
+            notes[0]
+        );
+
+        let res = response(
+            200,
+            &[("x-oauth-scopes", "repo, read:org")],
+            r#"{"login":"octocat"}"#,
+        );
+        let check = TokenCheck::from_response("ghp_abc", &res).unwrap();
+        assert!(check.guidance().is_empty());
+    }
+
+    #[test]
+    fn recognises_token_kinds() {
+        let res = response(200, &[], r#"{"login":"octocat"}"#);
+        let check = TokenCheck::from_response("github_pat_abc", &res).unwrap();
+        assert_eq!(check.kind, TokenKind::FineGrained);
+
+        let res = response(
+            403,
+            &[],
+            r#"{"message":"Resource not accessible by integration"}"#,
+        );
+        let check = TokenCheck::from_response("ghs_abc", &res).unwrap();
+        assert_eq!(check.kind, TokenKind::Installation);
+        assert_eq!(check.login, None);
+        assert_eq!(check.problem, None);
+
+        let res = response(
+            403,
+            &[
+                ("x-oauth-scopes", ""),
+                ("x-accepted-oauth-scopes", "read:user, user"),
+            ],
+            r#"{"message":"Forbidden"}"#,
+        );
+        let check = TokenCheck::from_response("ghp_abc", &res).unwrap();
+        let problem = check.problem.unwrap();
+        assert!(problem.contains("403: Forbidden"), "{problem}");
+        assert!(problem.contains("'read:user', 'user'"), "{problem}");
+    }
+
+    #[test]
+    fn explains_rejected_tokens() {
+        let res = response(401, &[], r#"{"message":"Bad credentials"}"#);
+        let err = TokenCheck::from_response("ghp_abc", &res).unwrap_err();
+        assert!(err.to_string().contains("401: Bad credentials"), "{err}");
+
+        let url = "https://github.com/orgs/acme/sso?authorization_request=abc";
+        let res = response(
+            403,
+            &[("x-github-sso", &format!("required; url={url}"))],
+            "{}",
+        );
+        let err = TokenCheck::from_response("ghp_abc", &res).unwrap_err();
+        assert!(err.to_string().contains(url), "{err}");
+    }
+}
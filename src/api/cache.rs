@@ -0,0 +1,173 @@
+use crate::utils::{self, DateTime};
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use time::Duration;
+
+/// Responses to queries over windows of time which ended at least this long ago
+/// are unlikely to change, and so are cached indefinitely.
+const HISTORICAL_AFTER: Duration = Duration::days(30);
+
+/// A cache of GraphQL responses on disk, so that running the summarizer several
+/// times in a row doesn't download everything each time.
+#[derive(Debug, Clone)]
+pub struct Cache {
+    dir: PathBuf,
+    ttl: Duration,
+    refresh: bool,
+}
+
+/// A cached response.
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    created_at: DateTime,
+    /// When this entry should no longer be used, or `None` if it can be used forever.
+    expires_at: Option<DateTime>,
+    query_name: String,
+    body: String,
+}
+
+/// Some figures about what's in the cache.
+#[derive(Debug, Clone, Default)]
+pub struct CacheStats {
+    pub entries: usize,
+    pub expired: usize,
+    pub historical: usize,
+    pub bytes: u64,
+}
+
+impl Cache {
+    /// A cache in the given directory. Entries expire after `ttl`, unless they are
+    /// historical. If `refresh` is true, cached entries are ignored, but new responses
+    /// are still saved.
+    pub fn new(dir: PathBuf, ttl: std::time::Duration, refresh: bool) -> Cache {
+        Cache {
+            dir,
+            ttl: Duration::try_from(ttl).unwrap_or(Duration::MAX),
+            refresh,
+        }
+    }
+
+    /// How long responses are cached for, unless they are historical.
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// The default cache directory; `$XDG_CACHE_HOME/github-summarizer`, falling back
+    /// to `$HOME/.cache/github-summarizer`.
+    pub fn default_dir() -> Option<PathBuf> {
+        let cache_home = std::env::var_os("XDG_CACHE_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))?;
+        Some(cache_home.join("github-summarizer"))
+    }
+
+    /// The key to cache a query under. `identity` identifies who we're authenticating
+    /// as, since what GitHub returns depends on what they can see.
+    pub fn key(api_url: &str, identity: &str, user: &str, body: &serde_json::Value) -> String {
+        utils::sha256_hex(format!("{api_url}\n{identity}\n{user}\n{body}").as_bytes())
+    }
+
+    /// Find a cached response, if one exists and has not expired.
+    pub async fn get(&self, key: &str) -> Option<String> {
+        if self.refresh {
+            return None;
+        }
+
+        let json = tokio::fs::read(self.path(key)).await.ok()?;
+        let entry: CacheEntry = serde_json::from_slice(&json).ok()?;
+        if entry.is_expired(DateTime::now()) {
+            return None;
+        }
+        Some(entry.body)
+    }
+
+    /// Cache a response to a query. `window_end` is the end of the window of time that
+    /// the query asked about, if any, and is used to decide whether it's historical.
+    pub async fn put(
+        &self,
+        key: &str,
+        query_name: &str,
+        window_end: Option<DateTime>,
+        body: &str,
+    ) -> Result<(), anyhow::Error> {
+        let now = DateTime::now();
+        let is_historical = window_end.is_some_and(|end| end.0 < now.0 - HISTORICAL_AFTER);
+        let entry = CacheEntry {
+            created_at: now,
+            expires_at: (!is_historical).then(|| DateTime(now.0.saturating_add(self.ttl))),
+            query_name: query_name.to_owned(),
+            body: body.to_owned(),
+        };
+
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .with_context(|| format!("Failed to create cache directory {}", self.dir.display()))?;
+        tokio::fs::write(self.path(key), serde_json::to_vec(&entry)?)
+            .await
+            .with_context(|| format!("Failed to write to cache in {}", self.dir.display()))?;
+        Ok(())
+    }
+
+    /// Remove everything from the cache in the given directory, returning
+    /// the number of entries removed.
+    pub fn clear(dir: &Path) -> Result<usize, anyhow::Error> {
+        let mut removed = 0;
+        for path in entry_paths(dir)? {
+            std::fs::remove_file(&path)
+                .with_context(|| format!("Failed to remove {}", path.display()))?;
+            removed += 1;
+        }
+        Ok(removed)
+    }
+
+    /// Work out some figures about the cache in the given directory.
+    pub fn stats(dir: &Path) -> Result<CacheStats, anyhow::Error> {
+        let now = DateTime::now();
+        let mut stats = CacheStats::default();
+        for path in entry_paths(dir)? {
+            let json = std::fs::read(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            stats.entries += 1;
+            stats.bytes += json.len() as u64;
+
+            match serde_json::from_slice::<CacheEntry>(&json) {
+                Ok(entry) if entry.expires_at.is_none() => stats.historical += 1,
+                Ok(entry) if !entry.is_expired(now) => {}
+                _ => stats.expired += 1,
+            }
+        }
+        Ok(stats)
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{key}.json"))
+    }
+}
+
+impl CacheEntry {
+    fn is_expired(&self, now: DateTime) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+/// The paths of all cache entries in a directory.
+fn entry_paths(dir: &Path) -> Result<Vec<PathBuf>, anyhow::Error> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to read cache in {}", dir.display()));
+        }
+    };
+
+    let mut paths = vec![];
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "json") {
+            paths.push(path);
+        }
+    }
+    Ok(paths)
+}
@@ -1,58 +1,101 @@
+use super::auth::{Auth, SsoStatus, TokenCheck};
+use super::cache::Cache;
+use super::paging::PageSize;
+use super::rate_limit::RateLimit;
+use super::recording::{RawResponse, RecordedRequest, Recording};
+use super::retry::RetryPolicy;
+use super::variables::{Variables, VariablesError};
 use anyhow::Context;
 use reqwest::Client;
-use serde::{Deserialize, Serialize, de::DeserializeOwned};
-use serde_json::{json, value::RawValue};
-
-const ROOT_URL: &str = "https://api.github.com/graphql";
+use reqwest::header::HeaderMap;
+use serde::{Deserialize, de::DeserializeOwned};
+use serde_json::json;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tokio::sync::Semaphore;
 
 /// A quick Github GraphQL API client.
 #[derive(Debug)]
 pub struct Api {
     client: Client,
-    token: String,
+    auth: Auth,
     user: String,
+    options: ApiOptions,
+    rate_limit: RateLimit,
+    warnings: Mutex<Vec<QueryError>>,
+    /// Whether the user has been told that the token isn't authorized for SAML single
+    /// sign-on with some organizations, so that they're only told once.
+    sso_noted: AtomicBool,
+    /// Limits how many requests are sent to GitHub at once.
+    in_flight: Semaphore,
 }
 
 impl Api {
-    pub async fn new(token: String, maybe_user: Option<String>) -> Result<Api, anyhow::Error> {
-        let client = Client::new();
-        let user = if let Some(user) = maybe_user {
-            user
-        } else {
-            Api::fetch_username(&client, &token).await?
+    pub async fn new(
+        auth: Auth,
+        maybe_user: Option<String>,
+        options: ApiOptions,
+    ) -> Result<Api, anyhow::Error> {
+        let mut api = Api {
+            auth,
+            client: Client::new(),
+            user: String::new(),
+            in_flight: Semaphore::new(options.concurrency.max(1)),
+            options,
+            rate_limit: RateLimit::default(),
+            warnings: Mutex::new(vec![]),
+            sso_noted: AtomicBool::new(false),
+        };
+
+        api.user = match (&api.auth, maybe_user) {
+            // Apps can't look up a user from their token; it'd just be the app.
+            (Auth::App(_), None) => {
+                return Err(anyhow::anyhow!(
+                    "--user must be given when authenticating as a GitHub App"
+                ));
+            }
+            // We know what an app's token can do, so there's nothing to check.
+            (Auth::App(_), Some(user)) => user,
+            (Auth::Token(_), maybe_user) => match (maybe_user, api.check_token().await?) {
+                (Some(user), _) => user,
+                (None, Some(login)) => login,
+                (None, None) => {
+                    return Err(anyhow::anyhow!(
+                        "Couldn't work out which user the token belongs to; use --user to say \
+                        whose contributions to summarize"
+                    ));
+                }
+            },
         };
 
-        Ok(Api {
-            token,
-            client,
-            user,
-        })
+        Ok(api)
     }
 
-    /// The username can not be retrieved via the GraphQL API, so we make a REST call instead.
-    async fn fetch_username(client: &Client, token: &str) -> Result<String, anyhow::Error> {
-        let res = client
-            .get("https://api.github.com/user")
-            .bearer_auth(&token)
-            .header("User-Agent", "jsdw-github-summarizer")
-            .header("X-GitHub-Api-Version", "2022-11-28")
-            .send()
+    /// Check that GitHub accepts our token, and let the user know about anything which
+    /// will stop us from seeing all of their contributions before we fetch any. Returns
+    /// the user that the token belongs to, if GitHub would tell us. The username can not
+    /// be retrieved via the GraphQL API, so we make a REST call for this.
+    async fn check_token(&self) -> Result<Option<String>, anyhow::Error> {
+        let res = self
+            .send(Endpoint::Rest("user"), None)
             .await
-            .with_context(|| "Failed to send request to get user")?;
+            .with_context(|| {
+                format!(
+                    "Failed to send request to get user from {}",
+                    self.options.url
+                )
+            })?;
 
-        if res.status().is_success() {
-            #[derive(Deserialize)]
-            struct UserResponse {
-                login: String,
-            }
-            let res: UserResponse = res
-                .json()
-                .await
-                .with_context(|| "Failed to decode user response")?;
-            Ok(res.login)
-        } else {
-            Err(anyhow::anyhow!("Failed to get user: {}", res.status()))
+        let token = self.auth.token(&self.client, &self.options.url).await?;
+        let check = TokenCheck::from_response(&token, &res)?;
+        self.sso_noted.store(check.sso.is_some(), Ordering::Relaxed);
+        for note in check.guidance() {
+            eprintln!("Note: {note}");
         }
+        Ok(check.login)
     }
 
     /// The API token user.
@@ -60,106 +103,426 @@ impl Api {
         &self.user
     }
 
-    /// Send a GraphQL query with variables.
+    /// Errors that GitHub reported alongside the data that it did return. Each of these
+    /// means that something is missing from the results.
+    pub fn warnings(&self) -> Vec<QueryError> {
+        self.warnings.lock().unwrap().clone()
+    }
+
+    /// Our GraphQL rate limit usage so far.
+    pub fn rate_limit(&self) -> &RateLimit {
+        &self.rate_limit
+    }
+
+    /// Send a GraphQL query which doesn't fetch a page of results, retrying if it fails for
+    /// some transient reason (including GitHub timing out).
     pub async fn query<Res: DeserializeOwned>(
         &self,
         query: &str,
         variables: Variables,
     ) -> Result<Res, anyhow::Error> {
-        let err_context = |msg: &str| {
-            // pull out the name given to the query if possible:
-            let first_line = query
-                .trim_start()
-                .lines()
-                .next()
-                .unwrap_or("<empty query>")
-                .trim_end()
-                .trim_end_matches('{')
-                .trim_end();
-            format!("{first_line}: {msg}")
+        self.query_with_retries(query, variables, true).await
+    }
+
+    /// Send a GraphQL query which fetches a page of results with the given page size, retrying
+    /// if it fails for some transient reason. If GitHub times out and a smaller page size could
+    /// be used, the error is returned straight away so that the caller can try that instead.
+    pub async fn query_page<Res: DeserializeOwned>(
+        &self,
+        query: &str,
+        variables: Variables,
+        page_size: PageSize,
+    ) -> Result<Res, anyhow::Error> {
+        self.query_with_retries(query, variables, page_size.is_smallest())
+            .await
+    }
+
+    /// Let the user know, once, if GitHub says that the token isn't authorized for SAML
+    /// single sign-on with organizations whose resources a query touched.
+    fn note_sso(&self, headers: &HeaderMap) {
+        if let Some(sso) = SsoStatus::from_headers(headers)
+            && !self.sso_noted.swap(true, Ordering::Relaxed)
+        {
+            eprintln!("Warning: {}", sso.guidance());
+        }
+    }
+
+    /// Send a GraphQL query with variables, retrying if it fails for some transient reason.
+    async fn query_with_retries<Res: DeserializeOwned>(
+        &self,
+        query: &str,
+        variables: Variables,
+        retry_timeouts: bool,
+    ) -> Result<Res, anyhow::Error> {
+        // pull out the name given to the query if possible:
+        let query_name = query
+            .trim_start()
+            .lines()
+            .next()
+            .unwrap_or("<empty query>")
+            .trim_end()
+            .trim_end_matches('{')
+            .trim_end();
+
+        let variables = variables
+            .build(query)
+            .map_err(ApiError::InvalidVariables)
+            .with_context(|| format!("{query_name}: Invalid variables"))?;
+        let body = json!({
+            "query": query,
+            "variables": variables
+        });
+
+        let cache_key = Cache::key(
+            self.options.url.graphql(),
+            &self.auth.identity(),
+            &self.user,
+            &body,
+        );
+        if let Some(cache) = &self.options.cache
+            && let Some(text) = cache.get(&cache_key).await
+        {
+            return decode_response(&text)
+                .map(|res| res.data)
+                .with_context(|| format!("{query_name}: Cached"));
+        }
+
+        let max_attempts = self.options.retry.max_attempts;
+        let mut attempt = 1;
+        loop {
+            match self.query_once(&body).await {
+                Ok((res, _)) if !res.errors.is_empty() => {
+                    // We got some data back, but not everything; carry on with what we have
+                    // and let the user know what's missing. Such responses aren't cached.
+                    for error in &res.errors {
+                        eprintln!("Warning: {query_name}: {error}");
+                    }
+                    self.warnings.lock().unwrap().extend(res.errors);
+                    return Ok(res.data);
+                }
+                Ok((res, text)) => {
+                    if let Some(cache) = &self.options.cache {
+                        // Queries over a window of time give its end as `to`.
+                        let window_end = body["variables"]["to"]
+                            .as_str()
+                            .and_then(|to| to.parse().ok());
+                        if let Err(e) = cache.put(&cache_key, query_name, window_end, &text).await {
+                            eprintln!("Warning: {e:#}");
+                        }
+                    }
+                    return Ok(res.data);
+                }
+                Err(e)
+                    if e.is_retryable()
+                        && (retry_timeouts || !e.is_timeout())
+                        && attempt < max_attempts =>
+                {
+                    let delay = self.options.retry.delay(attempt, e.retry_after());
+                    eprintln!(
+                        "{query_name}: {e}; retrying in {:.1}s (attempt {}/{max_attempts})",
+                        delay.as_secs_f64(),
+                        attempt + 1
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(e) => {
+                    return Err(e).with_context(|| {
+                        format!("{query_name}: Failed after {attempt} attempt(s)")
+                    });
+                }
+            }
+        }
+    }
+
+    /// Make a single attempt at sending a GraphQL query, returning the decoded
+    /// response alongside the original text of it.
+    async fn query_once<Res: DeserializeOwned>(
+        &self,
+        body: &serde_json::Value,
+    ) -> Result<(Decoded<Res>, String), ApiError> {
+        let res = loop {
+            self.rate_limit.wait_if_exhausted().await;
+
+            let res = self.send(Endpoint::GraphQl, Some(body)).await?;
+            self.rate_limit.update(&res.headers);
+            self.note_sso(&res.headers);
+
+            // If we've run out of rate limit, wait for it to reset and try again.
+            let status = res.status.as_u16();
+            if (status == 403 || status == 429) && self.rate_limit.is_exhausted() {
+                continue;
+            }
+
+            break res;
         };
 
-        let res = self
-            .client
-            .post(ROOT_URL)
-            .bearer_auth(&self.token)
+        let status = res.status.as_u16();
+        if !res.status.is_success() {
+            let retry_after = res
+                .headers
+                .get("retry-after")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse().ok())
+                .map(Duration::from_secs);
+            let body = res.body;
+
+            let is_rate_limited = status == 403 || status == 429;
+            if is_rate_limited && (retry_after.is_some() || body.contains("secondary rate limit")) {
+                return Err(ApiError::SecondaryRateLimit { retry_after, body });
+            }
+            return Err(ApiError::BadResponse(status, body));
+        }
+
+        let text = res.body;
+        let data = decode_response(&text)?;
+
+        // Queries ask for `rate_limit: rateLimit { cost }` so that we can track usage.
+        #[derive(Deserialize)]
+        struct QueryCost {
+            data: QueryCostData,
+        }
+        #[derive(Deserialize)]
+        struct QueryCostData {
+            rate_limit: Option<QueryCostRateLimit>,
+        }
+        #[derive(Deserialize)]
+        struct QueryCostRateLimit {
+            cost: u64,
+        }
+        if let Ok(QueryCost {
+            data: QueryCostData {
+                rate_limit: Some(rate_limit),
+            },
+        }) = serde_json::from_str(&text)
+        {
+            self.rate_limit.record_cost(rate_limit.cost);
+        }
+
+        Ok((data, text))
+    }
+
+    /// Send a request to GitHub, or replay the response to it if we're replaying a recording.
+    async fn send(
+        &self,
+        endpoint: Endpoint<'_>,
+        body: Option<&serde_json::Value>,
+    ) -> Result<RawResponse, ApiError> {
+        let (method, path) = match endpoint {
+            Endpoint::GraphQl => ("POST", "graphql"),
+            Endpoint::Rest(path) => ("GET", path),
+        };
+        let request = RecordedRequest {
+            method: method.to_owned(),
+            path: path.to_owned(),
+            body: body.cloned(),
+        };
+
+        if let Some(recording) = &self.options.recording
+            && let Some(res) = recording.replay(&request).await
+        {
+            return res.map_err(ApiError::RecordingError);
+        }
+
+        let _permit = self
+            .in_flight
+            .acquire()
+            .await
+            .expect("semaphore is never closed");
+
+        let req = match endpoint {
+            Endpoint::GraphQl => self.client.post(self.options.url.graphql()),
+            Endpoint::Rest(path) => self
+                .client
+                .get(self.options.url.rest(path))
+                .header("X-GitHub-Api-Version", "2022-11-28"),
+        };
+        let req = match body {
+            Some(body) => req.json(body),
+            None => req,
+        };
+        let token = self.auth.token(&self.client, &self.options.url).await?;
+        let res = req
+            .bearer_auth(token)
             .header("User-Agent", "jsdw-github-summarizer")
-            .json(&json!({
-                "query": query,
-                "variables": variables.build()
-            }))
             .send()
-            .await
-            .with_context(|| err_context("Failed to send request"))?;
+            .await?;
+
+        let res = RawResponse {
+            status: res.status(),
+            headers: res.headers().clone(),
+            body: res.text().await?,
+        };
 
-        let status = res.status();
-        if status.is_success() {
-            // Broken down the steps to allow better debugging in case of issue:
-            let text = res
-                .text()
+        if let Some(recording) = &self.options.recording {
+            recording
+                .record(&request, &res)
                 .await
-                .with_context(|| err_context("Failed to obtain string response"))?;
+                .map_err(ApiError::RecordingError)?;
+        }
 
-            #[derive(Deserialize)]
-            struct QueryData<Res> {
-                data: Res,
-            }
+        Ok(res)
+    }
+}
 
-            // Trying to decode as QueryData first, rather than trying to decode as an enum
-            // which can be data or errors, makes for much better error messages on decode fail.
-            let body: QueryData<Res> = serde_json::from_str(&text).map_err(|e| {
-                #[derive(Deserialize)]
-                struct QueryErrors {
-                    errors: Vec<QueryError>,
-                }
-                if let Ok(errors) = serde_json::from_str::<QueryErrors>(&text) {
-                    ApiError::QueryErrors(errors.errors)
-                } else {
-                    eprintln!("{text}");
-                    ApiError::DecodeError(anyhow::anyhow!("Failed to decode response: {}", e))
-                }
-            })?;
+/// A decoded GraphQL response. GitHub may return some data alongside errors, for
+/// instance if some of the items asked for could not be accessed.
+struct Decoded<Res> {
+    data: Res,
+    errors: Vec<QueryError>,
+}
 
-            Ok(body.data)
-        } else {
-            let body = res.text().await?;
-            Err(ApiError::BadResponse(status.as_u16(), body))
-                .with_context(|| err_context("Bad response making request"))
+/// Decode the body of a GraphQL response.
+fn decode_response<Res: DeserializeOwned>(text: &str) -> Result<Decoded<Res>, ApiError> {
+    #[derive(Deserialize)]
+    struct QueryData<Res> {
+        data: Res,
+    }
+    #[derive(Deserialize)]
+    struct QueryErrors {
+        #[serde(default)]
+        errors: Vec<QueryError>,
+    }
+
+    let errors = serde_json::from_str::<QueryErrors>(text)
+        .map(|e| e.errors)
+        .unwrap_or_default();
+
+    // Trying to decode as QueryData first, rather than trying to decode as an enum
+    // which can be data or errors, makes for much better error messages on decode fail.
+    match serde_json::from_str::<QueryData<Res>>(text) {
+        Ok(body) => Ok(Decoded {
+            data: body.data,
+            errors,
+        }),
+        Err(_) if !errors.is_empty() => Err(ApiError::from_query_errors(errors)),
+        Err(e) => {
+            eprintln!("{text}");
+            Err(ApiError::DecodeError(anyhow::anyhow!(
+                "Failed to decode response: {}",
+                e
+            )))
         }
     }
 }
 
-/// This represents variables you can pass to a GraphQL query.
-pub struct Variables {
-    json: Vec<u8>,
+/// Which GitHub API to send a request to.
+#[derive(Debug, Clone, Copy)]
+enum Endpoint<'a> {
+    GraphQl,
+    Rest(&'a str),
 }
 
-impl Variables {
-    pub fn new() -> Self {
-        Variables { json: Vec::new() }
+/// Configuration for an [`Api`] client.
+#[derive(Debug, Clone)]
+pub struct ApiOptions {
+    /// Where to find the GitHub APIs.
+    pub url: ApiUrl,
+    /// How to retry requests which fail for transient reasons.
+    pub retry: RetryPolicy,
+    /// Record the requests and responses we make, or replay them from a previous recording.
+    pub recording: Option<Recording>,
+    /// Cache responses on disk, and use them rather than asking GitHub again.
+    pub cache: Option<Cache>,
+    /// The most requests to have in flight to GitHub at once. GitHub discourages
+    /// lots of concurrent requests, and may hit us with secondary rate limits for them.
+    pub concurrency: usize,
+}
+
+impl Default for ApiOptions {
+    fn default() -> Self {
+        ApiOptions {
+            url: ApiUrl::default(),
+            retry: RetryPolicy::default(),
+            recording: None,
+            cache: None,
+            concurrency: 4,
+        }
     }
-    pub fn push<T: Serialize>(&mut self, name: &str, value: T) {
-        if self.json.is_empty() {
-            self.json.push(b'{');
-        } else {
-            self.json.push(b',');
+}
+
+/// Where to find the GitHub APIs. This is `https://api.github.com` for github.com. GitHub
+/// Enterprise Server instances serve their APIs under `/api/v3` (REST) and `/api/graphql`
+/// (GraphQL), and so a URL like `https://github.example.com/api/v3` or
+/// `https://github.example.com/api` is understood to point at one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiUrl {
+    graphql: String,
+    rest: String,
+}
+
+impl ApiUrl {
+    /// The GraphQL endpoint.
+    pub fn graphql(&self) -> &str {
+        &self.graphql
+    }
+
+    /// A REST endpoint, given a path like `user`.
+    pub fn rest(&self, path: &str) -> String {
+        format!("{}/{}", self.rest, path.trim_start_matches('/'))
+    }
+
+    /// The host that GitHub is served from, like `github.com`, which is what
+    /// credentials for it are stored against.
+    pub fn host(&self) -> String {
+        let Ok(url) = reqwest::Url::parse(&self.rest) else {
+            return String::new();
+        };
+        let host = match url.host_str() {
+            Some("api.github.com") => "github.com",
+            Some(host) => host,
+            None => "",
+        };
+        match url.port() {
+            Some(port) => format!("{host}:{port}"),
+            None => host.to_owned(),
         }
+    }
+}
 
-        serde_json::to_writer(&mut self.json, name).unwrap();
-        self.json.push(b':');
-        serde_json::to_writer(&mut self.json, &value).unwrap();
+impl Default for ApiUrl {
+    fn default() -> Self {
+        ApiUrl {
+            graphql: "https://api.github.com/graphql".to_owned(),
+            rest: "https://api.github.com".to_owned(),
+        }
     }
-    fn build(mut self) -> Option<Box<RawValue>> {
-        if self.json.is_empty() {
-            return None;
+}
+
+impl FromStr for ApiUrl {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let url =
+            reqwest::Url::parse(s.trim()).with_context(|| format!("Invalid API URL {s:?}"))?;
+        if url.scheme() != "https" && url.scheme() != "http" {
+            return Err(anyhow::anyhow!(
+                "Invalid API URL {s:?}: expected an http or https URL"
+            ));
         }
 
-        self.json.push(b'}');
+        let base = s.trim().trim_end_matches('/');
+        let ghes_root = base
+            .strip_suffix("/api/v3")
+            .or_else(|| base.strip_suffix("/api/graphql"))
+            .or_else(|| base.strip_suffix("/api"));
 
-        // Everything we push is valid UFT8 so this is fine.
-        let json = unsafe { String::from_utf8_unchecked(self.json) };
+        if let Some(root) = ghes_root {
+            Ok(ApiUrl {
+                graphql: format!("{root}/api/graphql"),
+                rest: format!("{root}/api/v3"),
+            })
+        } else {
+            Ok(ApiUrl {
+                graphql: format!("{base}/graphql"),
+                rest: base.to_owned(),
+            })
+        }
+    }
+}
 
-        Some(RawValue::from_string(json).unwrap())
+impl fmt::Display for ApiUrl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.rest)
     }
 }
 
@@ -169,28 +532,262 @@ pub enum ApiError {
     RequestError(#[from] reqwest::Error),
     #[error("{0} response: {1}")]
     BadResponse(u16, String),
-    #[error("Errors with query: {0:?}")]
+    #[error("Secondary rate limit hit: {body}")]
+    SecondaryRateLimit {
+        retry_after: Option<Duration>,
+        body: String,
+    },
+    #[error("Not found: {0}")]
+    NotFound(Box<QueryError>),
+    #[error("Forbidden: {0}")]
+    Forbidden(Box<QueryError>),
+    #[error("Rate limited: {0}")]
+    RateLimited(Box<QueryError>),
+    #[error("Query would return too many nodes: {0}")]
+    MaxNodeLimitExceeded(Box<QueryError>),
+    #[error("Errors with query: {}", display_list(.0))]
     QueryErrors(Vec<QueryError>),
     #[error("{0}")]
     DecodeError(#[from] anyhow::Error),
+    #[error("{0}")]
+    RecordingError(anyhow::Error),
+    #[error("{0}")]
+    AppAuth(String),
+    #[error("{0}")]
+    InvalidVariables(#[from] VariablesError),
+}
+
+impl ApiError {
+    /// The error to return when a query fails with the given errors. If GitHub told us
+    /// what kind of error one of them is, we return a more specific variant for it.
+    pub fn from_query_errors(mut errors: Vec<QueryError>) -> ApiError {
+        let known = errors.iter().enumerate().find_map(|(idx, error)| {
+            let variant: fn(Box<QueryError>) -> ApiError = match error.error_type.as_ref()? {
+                QueryErrorType::NotFound => ApiError::NotFound,
+                QueryErrorType::Forbidden => ApiError::Forbidden,
+                QueryErrorType::RateLimited => ApiError::RateLimited,
+                QueryErrorType::MaxNodeLimitExceeded => ApiError::MaxNodeLimitExceeded,
+                QueryErrorType::Other(_) => return None,
+            };
+            Some((idx, variant))
+        });
+
+        match known {
+            Some((idx, variant)) => variant(Box::new(errors.swap_remove(idx))),
+            None => ApiError::QueryErrors(errors),
+        }
+    }
+
+    /// Is this error likely to be transient, such that trying again may work?
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ApiError::RequestError(e) => {
+                e.is_connect() || e.is_timeout() || e.is_request() || e.is_body()
+            }
+            ApiError::BadResponse(status, _) => matches!(status, 500 | 502 | 503 | 504),
+            ApiError::SecondaryRateLimit { .. } => true,
+            // We'll wait for our rate limit to reset before the next attempt.
+            ApiError::RateLimited(_) => true,
+            ApiError::QueryErrors(_) => self.is_timeout(),
+            ApiError::NotFound(_)
+            | ApiError::Forbidden(_)
+            | ApiError::MaxNodeLimitExceeded(_)
+            | ApiError::DecodeError(_)
+            | ApiError::RecordingError(_)
+            | ApiError::AppAuth(_)
+            | ApiError::InvalidVariables(_) => false,
+        }
+    }
+
+    /// Did GitHub give up on the query because it took too long to run?
+    pub fn is_timeout(&self) -> bool {
+        match self {
+            // GitHub's gateway tends to give up on long-running queries like this.
+            ApiError::BadResponse(status, _) => matches!(status, 502 | 504),
+            ApiError::QueryErrors(errors) => errors.iter().any(|e| {
+                e.message
+                    .starts_with("Something went wrong while executing your query")
+            }),
+            _ => false,
+        }
+    }
+
+    /// How long GitHub asked us to wait before trying again, if it did.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            // GitHub asks that we wait at least a minute if no Retry-After is given.
+            ApiError::SecondaryRateLimit { retry_after, .. } => {
+                Some(retry_after.unwrap_or(Duration::from_secs(60)))
+            }
+            _ => None,
+        }
+    }
 }
 
-#[derive(Debug, Deserialize)]
+/// An error returned by GitHub alongside (or instead of) the data that we asked for.
+#[derive(Debug, Clone, Deserialize)]
 #[allow(dead_code)]
 pub struct QueryError {
-    path: Option<Vec<String>>,
-    message: String,
-}
-
-#[macro_export]
-macro_rules! variables {
-    ($($key:literal : $val:expr), *) => {{
-        // May be unused if empty; no params.
-        #[allow(unused_mut)]
-        let mut params = $crate::api::client::Variables::new();
-        $(
-            params.push($key, $val);
-        )*
-        params
-    }}
+    pub message: String,
+    /// The kind of error, if GitHub tells us.
+    #[serde(rename = "type", default)]
+    pub error_type: Option<QueryErrorType>,
+    /// Where in the response the error relates to.
+    #[serde(default)]
+    pub path: Option<Vec<PathSegment>>,
+    /// Where in the query the error relates to.
+    #[serde(default)]
+    pub locations: Vec<Location>,
+    /// Any additional details about the error.
+    #[serde(default)]
+    pub extensions: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+impl QueryError {
+    /// Does this error relate to the given top level field of the response?
+    pub fn is_at(&self, field: &str) -> bool {
+        matches!(
+            self.path.as_deref(),
+            Some([PathSegment::Field(f), ..]) if f == field
+        )
+    }
+}
+
+/// The kinds of error that GitHub returns from GraphQL queries.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(from = "String")]
+pub enum QueryErrorType {
+    NotFound,
+    Forbidden,
+    RateLimited,
+    MaxNodeLimitExceeded,
+    Other(String),
+}
+
+impl From<String> for QueryErrorType {
+    fn from(s: String) -> Self {
+        match s.as_str() {
+            "NOT_FOUND" => QueryErrorType::NotFound,
+            "FORBIDDEN" => QueryErrorType::Forbidden,
+            "RATE_LIMITED" => QueryErrorType::RateLimited,
+            "MAX_NODE_LIMIT_EXCEEDED" => QueryErrorType::MaxNodeLimitExceeded,
+            _ => QueryErrorType::Other(s),
+        }
+    }
+}
+
+/// A line and column in the query.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Paths point at the item in the response that an error relates to, and
+/// contain field names and indexes into lists.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum PathSegment {
+    Field(String),
+    Index(usize),
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let Some(path) = &self.path {
+            let path: Vec<String> = path
+                .iter()
+                .map(|segment| match segment {
+                    PathSegment::Field(field) => field.clone(),
+                    PathSegment::Index(idx) => idx.to_string(),
+                })
+                .collect();
+            write!(f, " (at {})", path.join("."))?;
+        } else if let Some(location) = self.locations.first() {
+            write!(
+                f,
+                " (at line {}, column {} of the query)",
+                location.line, location.column
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Display a list of errors on one line.
+fn display_list(errors: &[QueryError]) -> String {
+    let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+    errors.join("; ")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn query_errors_map_to_specific_variants() {
+        let errors: Vec<QueryError> = serde_json::from_value(json!([
+            {
+                "message": "Something else",
+                "extensions": { "code": "undefinedField" }
+            },
+            {
+                "type": "NOT_FOUND",
+                "path": ["user", "repositories", 3],
+                "locations": [{ "line": 2, "column": 5 }],
+                "message": "Could not resolve to a User with the login of 'nobody'."
+            }
+        ]))
+        .unwrap();
+
+        let ApiError::NotFound(error) = ApiError::from_query_errors(errors.clone()) else {
+            panic!("expected a NotFound error");
+        };
+        assert!(error.is_at("user"));
+        assert_eq!(error.locations[0].line, 2);
+        assert_eq!(
+            error.to_string(),
+            "Could not resolve to a User with the login of 'nobody'. (at user.repositories.3)"
+        );
+
+        // Errors that GitHub doesn't give a known type for are kept as they are.
+        assert!(matches!(
+            ApiError::from_query_errors(errors[..1].to_vec()),
+            ApiError::QueryErrors(errors) if errors.len() == 1
+        ));
+    }
+
+    #[test]
+    fn api_urls_for_github_and_ghes() {
+        let github: ApiUrl = "https://api.github.com/".parse().unwrap();
+        assert_eq!(github, ApiUrl::default());
+
+        for url in [
+            "https://ghe.example.com/api/v3",
+            "https://ghe.example.com/api/",
+            "https://ghe.example.com/api/graphql",
+        ] {
+            let ghes: ApiUrl = url.parse().unwrap();
+            assert_eq!(ghes.graphql(), "https://ghe.example.com/api/graphql");
+            assert_eq!(ghes.rest("/user"), "https://ghe.example.com/api/v3/user");
+        }
+
+        assert_eq!(github.host(), "github.com");
+        assert_eq!(
+            "https://ghe.example.com/api/v3"
+                .parse::<ApiUrl>()
+                .unwrap()
+                .host(),
+            "ghe.example.com"
+        );
+
+        let local: ApiUrl = "http://127.0.0.1:1234".parse().unwrap();
+        assert_eq!(local.host(), "127.0.0.1:1234");
+        assert_eq!(local.graphql(), "http://127.0.0.1:1234/graphql");
+        assert_eq!(local.rest("user"), "http://127.0.0.1:1234/user");
+
+        assert!("ftp://example.com".parse::<ApiUrl>().is_err());
+        assert!("not a url".parse::<ApiUrl>().is_err());
+    }
 }
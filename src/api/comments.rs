@@ -0,0 +1,197 @@
+use crate::api::client::Api;
+use crate::api::contributions::QueryRepository;
+use crate::api::paging::{self, Fetched};
+use crate::utils::DateTime;
+use crate::variables;
+use futures::{TryStreamExt, future};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// What comments on issues and pull requests are called in messages about those skipped.
+pub const NAME: &str = "comments";
+/// Comments in private repositories are only visible to tokens with these scopes.
+pub const SCOPES: &[&str] = &["repo"];
+
+/// The query which fetches a page of the comments that a user has left on issues and pull
+/// requests, most recently updated first. GitHub can't filter these by date.
+const QUERY: &str = r#"
+    query IssueComments($user:String!, $cursor:String, $first:Int!) {
+        rate_limit: rateLimit { cost }
+        user(login:$user) {
+            issue_comments: issueComments(first:$first, after:$cursor, orderBy:{field:UPDATED_AT, direction:DESC}) {
+                page_info: pageInfo {
+                    end_cursor: endCursor,
+                    has_next_page: hasNextPage
+                }
+                nodes {
+                    body_text: bodyText,
+                    created_at: createdAt,
+                    updated_at: updatedAt,
+                    url,
+                    issue {
+                        repository {
+                            name,
+                            owner { login }
+                        },
+                        number,
+                        title,
+                        url
+                    }
+                    pull_request: pullRequest {
+                        url
+                    }
+                }
+            }
+        }
+    }
+"#;
+
+#[derive(Deserialize)]
+struct QueryResult {
+    user: QueryUser,
+}
+
+#[derive(Deserialize)]
+struct QueryUser {
+    issue_comments: paging::Connection<CommentNode>,
+}
+
+#[derive(Deserialize)]
+struct CommentNode {
+    body_text: String,
+    created_at: DateTime,
+    updated_at: DateTime,
+    url: String,
+    issue: IssueInfo,
+    /// Set if the issue is really a pull request.
+    pull_request: Option<PullRequestInfo>,
+}
+
+#[derive(Deserialize)]
+struct IssueInfo {
+    repository: QueryRepository,
+    number: usize,
+    title: String,
+    url: String,
+}
+
+#[derive(Deserialize)]
+struct PullRequestInfo {
+    url: String,
+}
+
+/// An issue or pull request, along with the comments that a user left on it.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct CommentedItem {
+    pub kind: CommentedItemKind,
+    pub repository: String,
+    pub owner: String,
+    pub number: usize,
+    pub title: String,
+    pub url: String,
+    /// The comments left, oldest first.
+    pub comments: Vec<Comment>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CommentedItemKind {
+    Issue,
+    PullRequest,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Comment {
+    pub body_text: String,
+    pub created_at: DateTime,
+    pub url: String,
+}
+
+/// Fetch the comments that the user left on issues and pull requests between the dates
+/// given, grouped by the issue or pull request that they were left on. Those most recently
+/// commented on come first.
+pub async fn query(
+    api: &Api,
+    created_after: DateTime,
+    created_before: DateTime,
+) -> Result<Fetched<CommentedItem>, anyhow::Error> {
+    let user = api.user();
+    let comments = paging::paginate(
+        api,
+        QUERY.to_owned(),
+        move || variables!("user": user),
+        |res: QueryResult| res.user.issue_comments,
+        None,
+    );
+
+    // Comments can't have been created after they were last updated, so we can stop
+    // paging as soon as we get to those last updated before the window.
+    let comments: Vec<Option<CommentNode>> = comments
+        .try_take_while(|node| {
+            let in_range = node
+                .as_ref()
+                .is_none_or(|node| node.updated_at >= created_after);
+            future::ready(Ok(in_range))
+        })
+        .try_collect()
+        .await?;
+
+    let mut skipped = 0;
+    let mut items: HashMap<String, CommentedItem> = HashMap::new();
+    for node in comments {
+        let Some(node) = node else {
+            skipped += 1;
+            continue;
+        };
+        if node.created_at < created_after || node.created_at > created_before {
+            continue;
+        }
+
+        let (kind, url) = match node.pull_request {
+            Some(pr) => (CommentedItemKind::PullRequest, pr.url),
+            None => (CommentedItemKind::Issue, node.issue.url),
+        };
+        let item = items.entry(url.clone()).or_insert_with(|| CommentedItem {
+            kind,
+            repository: node.issue.repository.name,
+            owner: node.issue.repository.owner.login,
+            number: node.issue.number,
+            title: node.issue.title,
+            url,
+            comments: vec![],
+        });
+        item.comments.push(Comment {
+            body_text: node.body_text,
+            created_at: node.created_at,
+            url: node.url,
+        });
+    }
+
+    let mut items: Vec<CommentedItem> = items.into_values().collect();
+    for item in &mut items {
+        item.comments.sort_by_key(|comment| comment.created_at);
+    }
+    let last_comment = |item: &CommentedItem| item.comments.last().map(|c| c.created_at);
+    items.sort_by(|a, b| {
+        last_comment(b)
+            .cmp(&last_comment(a))
+            .then_with(|| a.url.cmp(&b.url))
+    });
+
+    Ok(Fetched { items, skipped })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::api::schema;
+
+    #[test]
+    fn check_queries() {
+        schema::check_queries(&[(QUERY, &|response| {
+            let res: QueryResult = serde_json::from_value(response)?;
+            assert_eq!(res.user.issue_comments.nodes.len(), 1);
+            Ok(())
+        })]);
+    }
+}
@@ -0,0 +1,385 @@
+use crate::api::client::Api;
+use crate::api::contributions::QueryRepositoryOwner;
+use crate::api::paging::{self, Connection};
+use crate::utils::DateTime;
+use crate::variables;
+use futures::{TryStreamExt, future};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+
+/// What commits are called in warnings about them.
+pub const NAME: &str = "commits";
+/// Commits to private repositories are only counted for tokens with these scopes.
+pub const SCOPES: &[&str] = &["repo"];
+
+/// The most repositories that GitHub will give back commits to for each window.
+const MAX_REPOSITORIES: usize = 100;
+
+/// The query which fetches how many commits a user made to each repository on each day.
+/// GitHub gives back at most [`MAX_REPOSITORIES`] repositories.
+const CONTRIBUTIONS_QUERY: &str = r#"
+    query CommitContributions($user:String!, $from:DateTime!, $to:DateTime!, $maxRepositories:Int!) {
+        rate_limit: rateLimit { cost }
+        user(login:$user) {
+            id
+            contributions_collection: contributionsCollection(from:$from, to:$to) {
+                commit_contributions_by_repository: commitContributionsByRepository(maxRepositories:$maxRepositories) {
+                    repository {
+                        name,
+                        owner { login },
+                        url
+                    }
+                    contributions(first:100) {
+                        nodes {
+                            commit_count: commitCount,
+                            occurred_at: occurredAt
+                        }
+                    }
+                }
+            }
+        }
+    }
+"#;
+
+/// The query which fetches a page of the commits that a user authored on a repository's
+/// default branch.
+const HISTORY_QUERY: &str = r#"
+    query CommitHistory($owner:String!, $name:String!, $author:CommitAuthor!, $from:GitTimestamp!, $to:GitTimestamp!, $cursor:String, $first:Int!) {
+        rate_limit: rateLimit { cost }
+        repository(owner:$owner, name:$name) {
+            default_branch_ref: defaultBranchRef {
+                target {
+                    ... on Commit {
+                        history(author:$author, since:$from, until:$to, first:$first, after:$cursor) {
+                            page_info: pageInfo {
+                                end_cursor: endCursor,
+                                has_next_page: hasNextPage
+                            }
+                            nodes {
+                                message_headline: messageHeadline,
+                                committed_date: committedDate,
+                                url
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+"#;
+
+/// Each repository has at most one contribution per day, so windows which span no more
+/// than 100 days fit into a single page of them.
+const WINDOW: time::Duration = time::Duration::days(99);
+/// The gap left between one window and the next, so that nothing is counted twice.
+const WINDOW_GAP: time::Duration = time::Duration::seconds(1);
+
+#[derive(Deserialize)]
+struct ContributionsResult {
+    user: ContributionsUser,
+}
+
+#[derive(Deserialize)]
+struct ContributionsUser {
+    id: String,
+    contributions_collection: ContributionsCollection,
+}
+
+#[derive(Deserialize)]
+struct ContributionsCollection {
+    commit_contributions_by_repository: Vec<ContributionsByRepository>,
+}
+
+#[derive(Deserialize)]
+struct ContributionsByRepository {
+    repository: ContributionsRepository,
+    contributions: CommitContributions,
+}
+
+/// Windows are short enough that there's only ever one page of these.
+#[derive(Deserialize)]
+struct CommitContributions {
+    nodes: Vec<Option<CommitContributionNode>>,
+}
+
+#[derive(Deserialize)]
+struct ContributionsRepository {
+    name: String,
+    owner: QueryRepositoryOwner,
+    url: String,
+}
+
+#[derive(Deserialize)]
+struct CommitContributionNode {
+    commit_count: usize,
+    occurred_at: DateTime,
+}
+
+#[derive(Deserialize)]
+struct HistoryResult {
+    repository: Option<HistoryRepository>,
+}
+
+#[derive(Deserialize)]
+struct HistoryRepository {
+    /// Empty repositories have no default branch.
+    default_branch_ref: Option<HistoryRef>,
+}
+
+#[derive(Deserialize)]
+struct HistoryRef {
+    target: Option<HistoryTarget>,
+}
+
+#[derive(Deserialize)]
+struct HistoryTarget {
+    history: Connection<CommitNode>,
+}
+
+#[derive(Deserialize)]
+struct CommitNode {
+    message_headline: String,
+    committed_date: DateTime,
+    url: String,
+}
+
+/// The commits that a user made to each repository.
+#[derive(Debug, Clone, Default)]
+pub struct Commits {
+    /// Repositories with the most commits come first.
+    pub repositories: Vec<RepositoryCommits>,
+    /// Whether GitHub gave back as many repositories as it will for some window of time,
+    /// in which case commits to others may be missing.
+    pub truncated: bool,
+}
+
+/// The commits that a user made to a repository.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct RepositoryCommits {
+    pub repository: String,
+    pub owner: String,
+    pub url: String,
+    pub commit_count: usize,
+    /// The days on which commits were made, in order.
+    pub days: Vec<CommitDay>,
+    /// The commits authored on the default branch, if they were asked for.
+    pub commits: Vec<Commit>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct CommitDay {
+    pub date: DateTime,
+    pub commit_count: usize,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Commit {
+    pub message_headline: String,
+    pub committed_at: DateTime,
+    pub url: String,
+}
+
+/// Fetch the number of commits that the user made to each repository on each day between
+/// the dates given, and optionally the commits they authored on each repository's default
+/// branch in that time.
+pub async fn query(
+    api: &Api,
+    created_after: DateTime,
+    created_before: DateTime,
+    with_messages: bool,
+) -> Result<Commits, anyhow::Error> {
+    let windows = windows(created_after, created_before)
+        .into_iter()
+        .map(|(from, to)| {
+            let vars = variables!(
+                "user": api.user(),
+                "from": from,
+                "to": to,
+                "maxRepositories": MAX_REPOSITORIES
+            );
+            api.query::<ContributionsResult>(CONTRIBUTIONS_QUERY, vars)
+        });
+    let windows = future::try_join_all(windows).await?;
+    let Some(user_id) = windows.first().map(|res| res.user.id.clone()) else {
+        return Ok(Commits::default());
+    };
+    let truncated = windows.iter().any(|res| {
+        res.user
+            .contributions_collection
+            .commit_contributions_by_repository
+            .len()
+            >= MAX_REPOSITORIES
+    });
+    if truncated {
+        eprintln!(
+            "Warning: {NAME}: GitHub gave back commits to only {MAX_REPOSITORIES} repositories \
+            for some windows of time, so commits to others may be missing"
+        );
+    }
+
+    let mut repositories: HashMap<String, (ContributionsRepository, BTreeMap<DateTime, usize>)> =
+        HashMap::new();
+    for by_repository in windows.into_iter().flat_map(|res| {
+        res.user
+            .contributions_collection
+            .commit_contributions_by_repository
+    }) {
+        let (_, days) = repositories
+            .entry(by_repository.repository.url.clone())
+            .or_insert_with(|| (by_repository.repository, BTreeMap::new()));
+        for node in by_repository.contributions.nodes.into_iter().flatten() {
+            *days.entry(node.occurred_at).or_default() += node.commit_count;
+        }
+    }
+
+    let mut repositories: Vec<RepositoryCommits> = repositories
+        .into_values()
+        .map(|(repository, days)| RepositoryCommits {
+            repository: repository.name,
+            owner: repository.owner.login,
+            url: repository.url,
+            commit_count: days.values().sum(),
+            days: days
+                .into_iter()
+                .map(|(date, commit_count)| CommitDay { date, commit_count })
+                .collect(),
+            commits: vec![],
+        })
+        .collect();
+    repositories.sort_by(|a, b| {
+        b.commit_count
+            .cmp(&a.commit_count)
+            .then_with(|| a.url.cmp(&b.url))
+    });
+
+    if with_messages {
+        let histories = repositories.iter().map(|repo| {
+            history(
+                api,
+                &repo.owner,
+                &repo.repository,
+                &user_id,
+                created_after,
+                created_before,
+            )
+        });
+        let histories = future::try_join_all(histories).await?;
+        for (repo, commits) in repositories.iter_mut().zip(histories) {
+            repo.commits = commits;
+        }
+    }
+
+    Ok(Commits {
+        repositories,
+        truncated,
+    })
+}
+
+/// Split the range `from..to` into windows which each fit into a single query. GitHub counts
+/// commits by the day, so windows are split at midnight to keep each day in just one of them.
+fn windows(from: DateTime, to: DateTime) -> Vec<(DateTime, DateTime)> {
+    let mut windows = vec![];
+    let mut start = from;
+    while start < to {
+        let midnight = start
+            .0
+            .to_offset(time::UtcOffset::UTC)
+            .replace_time(time::Time::MIDNIGHT);
+        let next = DateTime(midnight.saturating_add(WINDOW));
+        if next >= to {
+            windows.push((start, to));
+            break;
+        }
+        windows.push((start, DateTime(next.0 - WINDOW_GAP)));
+        start = next;
+    }
+    windows
+}
+
+/// Fetch the commits that the user authored on a repository's default branch between the
+/// dates given, oldest first.
+async fn history(
+    api: &Api,
+    owner: &str,
+    name: &str,
+    user_id: &str,
+    created_after: DateTime,
+    created_before: DateTime,
+) -> Result<Vec<Commit>, anyhow::Error> {
+    let author = serde_json::json!({ "id": user_id });
+    let commits = paging::paginate(
+        api,
+        HISTORY_QUERY.to_owned(),
+        move || {
+            variables!(
+                "owner": owner,
+                "name": name,
+                "author": &author,
+                "from": created_after,
+                "to": created_before
+            )
+        },
+        |res: HistoryResult| {
+            res.repository
+                .and_then(|repo| repo.default_branch_ref)
+                .and_then(|branch| branch.target)
+                .map_or_else(Connection::empty, |target| target.history)
+        },
+        None,
+    );
+
+    let mut commits: Vec<Commit> = commits
+        .try_filter_map(|node| future::ready(Ok(node)))
+        .map_ok(|node| Commit {
+            message_headline: node.message_headline,
+            committed_at: node.committed_date,
+            url: node.url,
+        })
+        .try_collect()
+        .await?;
+    commits.sort_by_key(|commit| commit.committed_at);
+    Ok(commits)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::api::schema;
+
+    fn dt(s: &str) -> DateTime {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn windows_split_days_between_them() {
+        assert_eq!(
+            windows(dt("2025-01-01T12:00:00Z"), dt("2025-06-01T00:00:00Z")),
+            vec![
+                (dt("2025-01-01T12:00:00Z"), dt("2025-04-09T23:59:59Z")),
+                (dt("2025-04-10T00:00:00Z"), dt("2025-06-01T00:00:00Z")),
+            ]
+        );
+
+        let short = windows(dt("2025-01-01T00:00:00Z"), dt("2025-01-02T00:00:00Z"));
+        assert_eq!(short.len(), 1);
+
+        assert!(windows(dt("2025-01-02T00:00:00Z"), dt("2025-01-01T00:00:00Z")).is_empty());
+    }
+
+    #[test]
+    fn check_queries() {
+        schema::check_queries(&[
+            (CONTRIBUTIONS_QUERY, &|response| {
+                let res: ContributionsResult = serde_json::from_value(response)?;
+                let repositories = res.user.contributions_collection;
+                assert_eq!(repositories.commit_contributions_by_repository.len(), 1);
+                Ok(())
+            }),
+            (HISTORY_QUERY, &|response| {
+                let res: HistoryResult = serde_json::from_value(response)?;
+                assert!(res.repository.is_some());
+                Ok(())
+            }),
+        ]);
+    }
+}
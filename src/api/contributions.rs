@@ -0,0 +1,468 @@
+use crate::api::client::Api;
+use crate::api::paging::{self, Connection, Fetched, PageSize};
+use crate::utils::{self, DateTime};
+use crate::variables;
+use anyhow::Context;
+use futures::future::Either;
+use futures::{Stream, StreamExt, TryStreamExt, future, stream};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use std::collections::{BTreeMap, HashSet};
+
+/// A kind of contribution which can be found in a user's `contributionsCollection`, like
+/// the pull requests that they've opened. Implementing this is all that's needed to fetch
+/// a new kind of contribution with [`query`].
+pub trait ContributionKind {
+    /// What these contributions are called, for messages.
+    const NAME: &'static str;
+    /// The name of the GraphQL query which fetches pages of them after the first.
+    const QUERY_NAME: &'static str;
+    /// The connection on `contributionsCollection` which they're found in, as an alias and
+    /// field, like `pull_request_contributions: pullRequestContributions`.
+    const CONNECTION: &'static str;
+    /// The fields to select on each node of the connection.
+    const NODE_FIELDS: &'static str;
+    /// The classic token scopes needed to see all of them.
+    const SCOPES: &'static [&'static str];
+
+    /// A node of the connection.
+    type Node: DeserializeOwned + 'static;
+    /// What we turn each node into.
+    type Item: 'static;
+
+    /// Turn a node into an item, or return `None` if GitHub couldn't give us it.
+    fn item(node: Self::Node) -> Option<Self::Item>;
+    /// A URL which uniquely identifies an item.
+    fn url(item: &Self::Item) -> &str;
+    /// When an item was created.
+    fn created_at(item: &Self::Item) -> DateTime;
+}
+
+#[derive(Deserialize)]
+struct QueryResult<N> {
+    user: QueryUser<N>,
+}
+
+#[derive(Deserialize)]
+struct QueryUser<N> {
+    /// Connections are under the alias given to them, which depends on the kind of
+    /// contribution.
+    contributions_collection: BTreeMap<String, Connection<N>>,
+}
+
+/// The query which fetches a page of contributions of the given kind.
+pub fn query_text<K: ContributionKind>() -> String {
+    format!(
+        r#"
+    query {name}($user:String!, $from:DateTime!, $to:DateTime!, $cursor:String, $first:Int!) {{
+        rate_limit: rateLimit {{ cost }}
+        user(login:$user) {{
+            contributions_collection: contributionsCollection(from:$from, to:$to) {{
+                {connection}
+            }}
+        }}
+    }}
+"#,
+        name = K::QUERY_NAME,
+        connection = connection_text(K::CONNECTION, K::NODE_FIELDS, "first:$first, after:$cursor"),
+    )
+}
+
+/// The query which fetches the first page of each of the given kinds of contribution, along
+/// with what we can find out about the contributions that we can't see.
+pub fn first_pages_query_text(kinds: &[Batched]) -> String {
+    let connections: Vec<String> = kinds
+        .iter()
+        .map(|kind| connection_text(kind.connection, kind.node_fields, "first:$first"))
+        .collect();
+    format!(
+        r#"
+    query Contributions($user:String!, $from:DateTime!, $to:DateTime!, $first:Int!) {{
+        rate_limit: rateLimit {{ cost }}
+        user(login:$user) {{
+            contributions_collection: contributionsCollection(from:$from, to:$to) {{
+                restricted_contributions_count: restrictedContributionsCount,
+                has_any_restricted_contributions: hasAnyRestrictedContributions,
+                has_activity_in_the_past: hasActivityInThePast,
+                {connections}
+            }}
+        }}
+    }}
+"#,
+        connections = connections.join("\n                "),
+    )
+}
+
+/// The part of a query which asks for a page of some connection.
+fn connection_text(connection: &str, node_fields: &str, arguments: &str) -> String {
+    format!(
+        r#"{connection}({arguments}) {{
+                    page_info: pageInfo {{
+                        end_cursor: endCursor,
+                        has_next_page: hasNextPage
+                    }}
+                    nodes {{
+                        {fields}
+                    }}
+                }}"#,
+        fields = node_fields.trim(),
+    )
+}
+
+/// A kind of contribution to fetch the first page of with [`FirstPages::fetch`].
+#[derive(Debug, Clone, Copy)]
+pub struct Batched {
+    connection: &'static str,
+    node_fields: &'static str,
+}
+
+impl Batched {
+    pub const fn of<K: ContributionKind>() -> Self {
+        Batched {
+            connection: K::CONNECTION,
+            node_fields: K::NODE_FIELDS,
+        }
+    }
+}
+
+/// The first page of several kinds of contribution, over each window of time between two
+/// dates. These are all fetched with one query per window, so that [`query`] and [`stream`]
+/// only need to send more queries for those kinds which have more than a page of them.
+#[derive(Debug)]
+pub struct FirstPages {
+    windows: Vec<FirstPagesWindow>,
+}
+
+#[derive(Debug)]
+struct FirstPagesWindow {
+    from: DateTime,
+    to: DateTime,
+    restricted: Restricted,
+    has_activity_in_the_past: bool,
+    /// Each page is under the alias given to its connection. Nodes are decoded once we
+    /// know which kind of contribution they are.
+    connections: BTreeMap<String, Connection<serde_json::Value>>,
+}
+
+/// The contributions that a user made in private repositories which the token can't see.
+/// GitHub only tells us about these if the user has chosen to share counts of their
+/// private contributions.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Restricted {
+    pub count: usize,
+    /// Whether there were any, even if GitHub didn't count them.
+    pub any: bool,
+}
+
+#[derive(Deserialize)]
+struct FirstPagesResult {
+    user: FirstPagesUser,
+}
+
+#[derive(Deserialize)]
+struct FirstPagesUser {
+    contributions_collection: FirstPagesCollection,
+}
+
+#[derive(Deserialize)]
+struct FirstPagesCollection {
+    restricted_contributions_count: usize,
+    has_any_restricted_contributions: bool,
+    has_activity_in_the_past: bool,
+    /// Anything else is a page of a connection, under the alias given to it.
+    #[serde(flatten)]
+    connections: BTreeMap<String, Connection<serde_json::Value>>,
+}
+
+impl FirstPages {
+    /// Fetch the first page of each of the given kinds of contribution which were created
+    /// between the dates given.
+    pub async fn fetch(
+        api: &Api,
+        kinds: &[Batched],
+        created_after: DateTime,
+        created_before: DateTime,
+    ) -> Result<FirstPages, anyhow::Error> {
+        let query = first_pages_query_text(kinds);
+        let windows = utils::year_windows(created_after, created_before)
+            .into_iter()
+            .map(|(from, to)| FirstPages::fetch_window(api, &query, from, to));
+        let windows = future::try_join_all(windows).await?;
+        Ok(FirstPages { windows })
+    }
+
+    /// The contributions made between the dates given which the token can't see.
+    pub fn restricted(&self) -> Restricted {
+        self.windows
+            .iter()
+            .fold(Restricted::default(), |total, window| Restricted {
+                count: total.count + window.restricted.count,
+                any: total.any || window.restricted.any,
+            })
+    }
+
+    /// Whether the user did anything on GitHub before the dates given.
+    pub fn has_activity_in_the_past(&self) -> bool {
+        self.windows
+            .first()
+            .is_none_or(|window| window.has_activity_in_the_past)
+    }
+
+    async fn fetch_window(
+        api: &Api,
+        query: &str,
+        from: DateTime,
+        to: DateTime,
+    ) -> Result<FirstPagesWindow, anyhow::Error> {
+        let mut page_size = PageSize::default();
+        loop {
+            let vars =
+                variables!("user": api.user(), "from": from, "to": to, "first": page_size.get());
+            match api
+                .query_page::<FirstPagesResult>(query, vars, page_size)
+                .await
+            {
+                Ok(res) => {
+                    let collection = res.user.contributions_collection;
+                    return Ok(FirstPagesWindow {
+                        from,
+                        to,
+                        restricted: Restricted {
+                            count: collection.restricted_contributions_count,
+                            any: collection.has_any_restricted_contributions,
+                        },
+                        has_activity_in_the_past: collection.has_activity_in_the_past,
+                        connections: collection.connections,
+                    });
+                }
+                Err(e) if page_size.shrink_on_timeout(&e) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Fetch the user's contributions of the given kind over the windows of time that the first
+/// pages were fetched for, sorted by when they were created.
+pub async fn query<K: ContributionKind>(
+    api: &Api,
+    first_pages: &FirstPages,
+) -> Result<Fetched<K::Item>, anyhow::Error> {
+    let mut fetched = Fetched::default();
+    let mut items = stream_windows::<K>(api, first_pages);
+    while let Some(item) = items.try_next().await? {
+        match item {
+            Some(item) => fetched.items.push(item),
+            None => fetched.skipped += 1,
+        }
+    }
+
+    // Windows share their boundaries, so the same item may turn up in two of them.
+    utils::dedup_by_key(&mut fetched.items, K::url);
+    fetched.items.sort_by_key(K::created_at);
+
+    Ok(fetched)
+}
+
+/// Stream the user's contributions of the given kind over the windows of time that the
+/// first pages were fetched for, as they are fetched. Unlike [`query`], these are in no
+/// particular order, and any that GitHub couldn't give us are left out (GitHub's reasons
+/// are in [`Api::warnings`]).
+pub fn stream<'a, K: ContributionKind>(
+    api: &'a Api,
+    first_pages: &FirstPages,
+) -> impl Stream<Item = Result<K::Item, anyhow::Error>> + use<'a, K> {
+    let mut seen = HashSet::new();
+    stream_windows::<K>(api, first_pages)
+        .try_filter_map(|item| future::ready(Ok(item)))
+        // Windows share their boundaries, so the same item may turn up in two of them.
+        .try_filter(move |item| future::ready(seen.insert(K::url(item).to_owned())))
+}
+
+/// GitHub won't return contributions over more than a year at once, so longer ranges are
+/// split into windows, the rest of which are fetched concurrently. Items that GitHub
+/// couldn't give us are `None`.
+fn stream_windows<'a, K: ContributionKind>(
+    api: &'a Api,
+    first_pages: &FirstPages,
+) -> impl Stream<Item = Result<Option<K::Item>, anyhow::Error>> + use<'a, K> {
+    let query = query_text::<K>();
+    let alias = K::CONNECTION.split(':').next().unwrap_or_default().trim();
+    let windows = first_pages.windows.iter().map(|window| {
+        let first_page = window.connections.get(alias).cloned();
+        Box::pin(stream_window::<K>(
+            api,
+            query.clone(),
+            window.from,
+            window.to,
+            first_page,
+        ))
+    });
+    stream::select_all(windows)
+}
+
+/// Stream the contributions in a window, starting with the first page if we have it and
+/// then fetching any more pages that there are.
+fn stream_window<K: ContributionKind>(
+    api: &Api,
+    query: String,
+    created_after: DateTime,
+    created_before: DateTime,
+    first_page: Option<Connection<serde_json::Value>>,
+) -> impl Stream<Item = Result<Option<K::Item>, anyhow::Error>> + '_ {
+    let (nodes, after, has_more) = match first_page {
+        Some(page) => {
+            let has_more = page.page_info.has_next_page && page.page_info.end_cursor.is_some();
+            (page.nodes, page.page_info.end_cursor, has_more)
+        }
+        None => (vec![], None, true),
+    };
+    let first = stream::iter(nodes).map(|node| {
+        node.map(serde_json::from_value::<K::Node>)
+            .transpose()
+            .with_context(|| format!("{}: Couldn't decode the first page", K::QUERY_NAME))
+    });
+
+    let rest = if has_more {
+        let user = api.user();
+        Either::Left(paging::paginate(
+            api,
+            query,
+            move || variables!("user": user, "from": created_after, "to": created_before),
+            |res: QueryResult<K::Node>| {
+                res.user
+                    .contributions_collection
+                    .into_values()
+                    .next()
+                    .expect("the query asks for one connection")
+            },
+            after,
+        ))
+    } else {
+        Either::Right(stream::empty())
+    };
+
+    first.chain(rest).map_ok(|node| node.and_then(K::item))
+}
+
+/// The repository that a contribution was made to.
+#[derive(Deserialize)]
+pub struct QueryRepository {
+    pub name: String,
+    pub owner: QueryRepositoryOwner,
+}
+
+#[derive(Deserialize)]
+pub struct QueryRepositoryOwner {
+    pub login: String,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::api::issues::Issues;
+    use crate::api::pull_request_reviews::PullRequestReviews;
+    use crate::api::pull_requests::PullRequests;
+    use crate::api::repositories::Repositories;
+    use crate::api::schema::Schema;
+
+    /// Check the query for a kind of contribution against GitHub's schema, and check that
+    /// responses to it decode, whether or not GitHub gives us the nullable fields.
+    fn check_query<K: ContributionKind>(schema: &Schema) {
+        let query = query_text::<K>();
+        if let Err(problems) = schema.check(&query) {
+            panic!("{} query is invalid:\n{}", K::NAME, problems.join("\n"));
+        }
+
+        for nulls in [false, true] {
+            let response = schema.sample_response(&query, nulls);
+            let res: QueryResult<K::Node> = serde_json::from_value(response.clone())
+                .unwrap_or_else(|e| {
+                    panic!("{} response doesn't decode: {e}\n{response:#}", K::NAME)
+                });
+            let connection = res
+                .user
+                .contributions_collection
+                .into_values()
+                .next()
+                .unwrap();
+            let node = connection.nodes.into_iter().next().unwrap().unwrap();
+            if !nulls {
+                assert!(
+                    K::item(node).is_some(),
+                    "{} node should be an item",
+                    K::NAME
+                );
+            }
+        }
+    }
+
+    /// Check that the first page of a kind of contribution can be found in a response to
+    /// the query for first pages, and decoded.
+    fn check_first_page<K: ContributionKind>(res: &FirstPagesResult) {
+        let alias = K::CONNECTION.split(':').next().unwrap().trim();
+        let connection = &res.user.contributions_collection.connections[alias];
+        let node = connection.nodes[0].clone().unwrap();
+        if let Err(e) = serde_json::from_value::<K::Node>(node) {
+            panic!("{} first page doesn't decode: {e}", K::NAME);
+        }
+    }
+
+    #[test]
+    fn check_queries() {
+        let schema = Schema::github();
+        check_query::<PullRequests>(&schema);
+        check_query::<PullRequestReviews>(&schema);
+        check_query::<Issues>(&schema);
+        check_query::<Repositories>(&schema);
+
+        let query = first_pages_query_text(&[
+            Batched::of::<PullRequests>(),
+            Batched::of::<PullRequestReviews>(),
+            Batched::of::<Issues>(),
+            Batched::of::<Repositories>(),
+        ]);
+        if let Err(problems) = schema.check(&query) {
+            panic!("first pages query is invalid:\n{}", problems.join("\n"));
+        }
+        let res: FirstPagesResult =
+            serde_json::from_value(schema.sample_response(&query, false)).unwrap();
+        assert_eq!(res.user.contributions_collection.connections.len(), 4);
+        check_first_page::<PullRequests>(&res);
+        check_first_page::<PullRequestReviews>(&res);
+        check_first_page::<Issues>(&res);
+        check_first_page::<Repositories>(&res);
+    }
+
+    #[test]
+    fn check_queries_catches_mistakes() {
+        let schema = Schema::github();
+        let query = r#"
+            query Broken($user:String, $first:String) {
+                user(login:$user) {
+                    contributions_collection: contributionsCollection {
+                        pullRequestContributions(first:$first, sort:ASC) {
+                            nodes { pullRequest { titel } }
+                        }
+                    }
+                }
+            }
+        "#;
+
+        assert_eq!(
+            schema.check(query),
+            Err(vec![
+                "/user: $user is a String but login takes a String!".to_owned(),
+                "/user/contributionsCollection/pullRequestContributions: \
+                 $first is a String but first takes a Int"
+                    .to_owned(),
+                "/user/contributionsCollection/pullRequestContributions: \
+                 there's no argument sort"
+                    .to_owned(),
+                "/user/contributionsCollection/pullRequestContributions/nodes/pullRequest/titel: \
+                 PullRequest has no field titel"
+                    .to_owned(),
+            ])
+        );
+    }
+}
@@ -0,0 +1,342 @@
+use crate::api::client::Api;
+use crate::api::contributions::QueryRepository;
+use crate::api::paging::{self, Fetched};
+use crate::utils::DateTime;
+use crate::variables;
+use futures::{Stream, TryStreamExt, future};
+use serde::Deserialize;
+
+/// What discussions are called in messages about those skipped.
+pub const NAME: &str = "discussions";
+/// Likewise for comments on discussions.
+pub const COMMENTS_NAME: &str = "discussion comments";
+/// Likewise for comments accepted as the answer to a discussion.
+pub const ANSWERS_NAME: &str = "discussion answers";
+/// Discussions in private repositories, and comments on them, are only visible to tokens
+/// with these scopes.
+pub const SCOPES: &[&str] = &["repo"];
+
+/// The query which fetches a page of the discussions that a user has started, newest
+/// first. GitHub can't filter these by date.
+const DISCUSSIONS_QUERY: &str = r#"
+    query RepositoryDiscussions($user:String!, $cursor:String, $first:Int!) {
+        rate_limit: rateLimit { cost }
+        user(login:$user) {
+            repository_discussions: repositoryDiscussions(first:$first, after:$cursor, orderBy:{field:CREATED_AT, direction:DESC}) {
+                page_info: pageInfo {
+                    end_cursor: endCursor,
+                    has_next_page: hasNextPage
+                }
+                nodes {
+                    repository {
+                        name,
+                        owner { login }
+                    },
+                    category { name },
+                    title,
+                    created_at: createdAt,
+                    body_text: bodyText,
+                    answer_chosen_at: answerChosenAt,
+                    url
+                }
+            }
+        }
+    }
+"#;
+
+/// The query which fetches a page of the comments that a user has left on discussions, or
+/// of just those which were accepted as the answer. GitHub can't filter these by date, or
+/// order them.
+const COMMENTS_QUERY: &str = r#"
+    query DiscussionComments($user:String!, $cursor:String, $first:Int!, $onlyAnswers:Boolean) {
+        rate_limit: rateLimit { cost }
+        user(login:$user) {
+            repository_discussion_comments: repositoryDiscussionComments(first:$first, after:$cursor, onlyAnswers:$onlyAnswers) {
+                page_info: pageInfo {
+                    end_cursor: endCursor,
+                    has_next_page: hasNextPage
+                }
+                nodes {
+                    discussion {
+                        repository {
+                            name,
+                            owner { login }
+                        },
+                        category { name },
+                        title,
+                        url
+                    }
+                    body_text: bodyText,
+                    created_at: createdAt,
+                    url
+                }
+            }
+        }
+    }
+"#;
+
+#[derive(Deserialize)]
+struct DiscussionsResult {
+    user: DiscussionsUser,
+}
+
+#[derive(Deserialize)]
+struct DiscussionsUser {
+    repository_discussions: paging::Connection<DiscussionNode>,
+}
+
+#[derive(Deserialize)]
+struct DiscussionNode {
+    repository: QueryRepository,
+    category: CategoryInfo,
+    title: String,
+    created_at: DateTime,
+    body_text: String,
+    answer_chosen_at: Option<DateTime>,
+    url: String,
+}
+
+#[derive(Deserialize)]
+struct CategoryInfo {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct CommentsResult {
+    user: CommentsUser,
+}
+
+#[derive(Deserialize)]
+struct CommentsUser {
+    repository_discussion_comments: paging::Connection<CommentNode>,
+}
+
+#[derive(Deserialize)]
+struct CommentNode {
+    /// Null if the discussion has since been deleted.
+    discussion: Option<CommentDiscussion>,
+    body_text: String,
+    created_at: DateTime,
+    url: String,
+}
+
+#[derive(Deserialize)]
+struct CommentDiscussion {
+    repository: QueryRepository,
+    category: CategoryInfo,
+    title: String,
+    url: String,
+}
+
+/// A discussion that a user started.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Discussion {
+    pub repository: String,
+    pub owner: String,
+    pub category: String,
+    pub title: String,
+    pub created_at: DateTime,
+    pub body_text: String,
+    /// When a comment was marked as the answer, if one has been.
+    pub answer_chosen_at: Option<DateTime>,
+    pub url: String,
+}
+
+/// A comment that a user left on a discussion.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct DiscussionComment {
+    pub repository: String,
+    pub owner: String,
+    pub category: String,
+    pub discussion_title: String,
+    pub discussion_url: String,
+    pub body_text: String,
+    pub created_at: DateTime,
+    pub url: String,
+}
+
+/// Fetch the discussions that the user started between the dates given, oldest first. As
+/// GitHub can't filter these by date, they're paged through newest first until we're past
+/// the dates given.
+pub async fn query(
+    api: &Api,
+    created_after: DateTime,
+    created_before: DateTime,
+) -> Result<Fetched<Discussion>, anyhow::Error> {
+    collect(discussions(api, created_after, created_before), |d| {
+        d.created_at
+    })
+    .await
+}
+
+/// Stream the discussions that the user started between the dates given, as they are
+/// fetched. Unlike [`query`], these are newest first, and any that GitHub couldn't give us
+/// are left out (GitHub's reasons are in [`Api::warnings`]).
+pub fn stream(
+    api: &Api,
+    created_after: DateTime,
+    created_before: DateTime,
+) -> impl Stream<Item = Result<Discussion, anyhow::Error>> + use<'_> {
+    discussions(api, created_after, created_before).try_filter_map(|d| future::ready(Ok(d)))
+}
+
+/// Fetch the comments that the user left on discussions between the dates given, oldest
+/// first. As these can't be ordered, all of the comments that the user has ever left on
+/// discussions are paged through, which takes a request for every 100 of them.
+pub async fn query_comments(
+    api: &Api,
+    created_after: DateTime,
+    created_before: DateTime,
+) -> Result<Fetched<DiscussionComment>, anyhow::Error> {
+    let comments = comments(api, false, created_after, created_before);
+    collect(comments, |c| c.created_at).await
+}
+
+/// Fetch the comments that the user left on discussions between the dates given which were
+/// accepted as the answer, oldest first.
+pub async fn query_answers(
+    api: &Api,
+    created_after: DateTime,
+    created_before: DateTime,
+) -> Result<Fetched<DiscussionComment>, anyhow::Error> {
+    let answers = comments(api, true, created_after, created_before);
+    collect(answers, |c| c.created_at).await
+}
+
+/// Stream the comments that the user left on discussions between the dates given, or just
+/// those which were accepted as the answer, as they are fetched. Unlike [`query_comments`]
+/// and [`query_answers`], these are in no particular order, and any that GitHub couldn't
+/// give us are left out.
+pub fn stream_comments(
+    api: &Api,
+    only_answers: bool,
+    created_after: DateTime,
+    created_before: DateTime,
+) -> impl Stream<Item = Result<DiscussionComment, anyhow::Error>> + use<'_> {
+    comments(api, only_answers, created_after, created_before)
+        .try_filter_map(|c| future::ready(Ok(c)))
+}
+
+/// Page through the discussions that the user started, stopping once we're past those
+/// started between the dates given. Discussions that GitHub couldn't give us are `None`.
+fn discussions(
+    api: &Api,
+    created_after: DateTime,
+    created_before: DateTime,
+) -> impl Stream<Item = Result<Option<Discussion>, anyhow::Error>> + use<'_> {
+    let user = api.user();
+    paging::paginate(
+        api,
+        DISCUSSIONS_QUERY.to_owned(),
+        move || variables!("user": user),
+        |res: DiscussionsResult| res.user.repository_discussions,
+        None,
+    )
+    // These are newest first, so we can stop paging once we're past the window.
+    .try_take_while(move |node| {
+        let in_range = node
+            .as_ref()
+            .is_none_or(|node| node.created_at >= created_after);
+        future::ready(Ok(in_range))
+    })
+    .try_filter(move |node| {
+        let in_range = node
+            .as_ref()
+            .is_none_or(|node| node.created_at <= created_before);
+        future::ready(in_range)
+    })
+    .map_ok(|node| {
+        node.map(|node| Discussion {
+            repository: node.repository.name,
+            owner: node.repository.owner.login,
+            category: node.category.name,
+            title: node.title,
+            created_at: node.created_at,
+            body_text: node.body_text,
+            answer_chosen_at: node.answer_chosen_at,
+            url: node.url,
+        })
+    })
+}
+
+/// Page through the comments that the user left on discussions, or just those which were
+/// accepted as the answer, keeping those created between the dates given. Comments that
+/// GitHub couldn't give us are `None`.
+fn comments(
+    api: &Api,
+    only_answers: bool,
+    created_after: DateTime,
+    created_before: DateTime,
+) -> impl Stream<Item = Result<Option<DiscussionComment>, anyhow::Error>> + use<'_> {
+    let user = api.user();
+    paging::paginate(
+        api,
+        COMMENTS_QUERY.to_owned(),
+        move || variables!("user": user, "onlyAnswers": only_answers),
+        |res: CommentsResult| res.user.repository_discussion_comments,
+        None,
+    )
+    .try_filter(move |node| {
+        let keep = node.as_ref().is_none_or(|node| {
+            // There's nothing to say about comments on discussions that have since been
+            // deleted, so they're left out rather than counted as skipped.
+            node.discussion.is_some()
+                && node.created_at >= created_after
+                && node.created_at <= created_before
+        });
+        future::ready(keep)
+    })
+    .map_ok(|node| {
+        let node = node?;
+        let discussion = node.discussion?;
+        Some(DiscussionComment {
+            repository: discussion.repository.name,
+            owner: discussion.repository.owner.login,
+            category: discussion.category.name,
+            discussion_title: discussion.title,
+            discussion_url: discussion.url,
+            body_text: node.body_text,
+            created_at: node.created_at,
+            url: node.url,
+        })
+    })
+}
+
+/// Collect the items given, oldest first, counting those that GitHub couldn't give us.
+async fn collect<T>(
+    items: impl Stream<Item = Result<Option<T>, anyhow::Error>>,
+    created_at: impl Fn(&T) -> DateTime,
+) -> Result<Fetched<T>, anyhow::Error> {
+    let mut fetched = Fetched::default();
+    let mut items = std::pin::pin!(items);
+    while let Some(item) = items.try_next().await? {
+        match item {
+            Some(item) => fetched.items.push(item),
+            None => fetched.skipped += 1,
+        }
+    }
+    fetched.items.sort_by_key(created_at);
+    Ok(fetched)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::api::schema;
+
+    #[test]
+    fn check_queries() {
+        schema::check_queries(&[
+            (DISCUSSIONS_QUERY, &|response| {
+                let res: DiscussionsResult = serde_json::from_value(response)?;
+                assert_eq!(res.user.repository_discussions.nodes.len(), 1);
+                Ok(())
+            }),
+            (COMMENTS_QUERY, &|response| {
+                let res: CommentsResult = serde_json::from_value(response)?;
+                assert_eq!(res.user.repository_discussion_comments.nodes.len(), 1);
+                Ok(())
+            }),
+        ]);
+    }
+}
@@ -1,4 +1,18 @@
+pub mod auth;
+pub mod cache;
 pub mod client;
+pub mod comments;
+pub mod commits;
+pub mod contributions;
+pub mod discussions;
 pub mod issues;
+pub mod paging;
+pub mod pull_request_reviews;
 pub mod pull_requests;
+pub mod rate_limit;
+pub mod recording;
 pub mod repositories;
+pub mod retry;
+#[cfg(test)]
+mod schema;
+pub mod variables;
@@ -0,0 +1,160 @@
+use super::client::{Api, ApiError};
+use super::variables::Variables;
+use futures::{Stream, TryStreamExt, stream};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+
+/// The page sizes that we'll try, from largest to smallest.
+const PAGE_SIZES: [u32; 4] = [100, 50, 25, 10];
+
+/// The number of items to ask for in each page of results. GitHub can time out when asked
+/// for large pages of big items (for instance PRs with long descriptions), so this shrinks
+/// when that happens and grows back again after each successful page.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PageSize {
+    idx: usize,
+}
+
+impl PageSize {
+    /// The current page size.
+    pub fn get(&self) -> u32 {
+        PAGE_SIZES[self.idx]
+    }
+
+    /// Is this the smallest page size that we'll try?
+    pub fn is_smallest(&self) -> bool {
+        self.idx + 1 >= PAGE_SIZES.len()
+    }
+
+    /// Use a smaller page size if the error indicates that the last page timed out.
+    /// Returns false if the error was some other error or we're already at the
+    /// smallest page size, in which case the error should be returned.
+    pub fn shrink_on_timeout(&mut self, err: &anyhow::Error) -> bool {
+        let is_timeout = err
+            .downcast_ref::<ApiError>()
+            .is_some_and(ApiError::is_timeout);
+
+        if !is_timeout || self.is_smallest() {
+            return false;
+        }
+
+        self.idx += 1;
+        eprintln!(
+            "GitHub timed out fetching a page of results; retrying with a page size of {}",
+            self.get()
+        );
+        true
+    }
+
+    /// Use a larger page size again, if we'd previously shrunk it.
+    pub fn grow(&mut self) {
+        self.idx = self.idx.saturating_sub(1);
+    }
+}
+
+/// The items fetched by paging through some query.
+#[derive(Debug, Clone)]
+pub struct Fetched<T> {
+    pub items: Vec<T>,
+    /// How many items GitHub couldn't give back to us, for instance because
+    /// the token being used can't access them.
+    pub skipped: usize,
+}
+
+impl<T> Default for Fetched<T> {
+    fn default() -> Self {
+        Fetched {
+            items: vec![],
+            skipped: 0,
+        }
+    }
+}
+
+/// A page of a GraphQL connection.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Connection<N> {
+    pub page_info: PageInfo,
+    /// Nodes are null if GitHub couldn't give them to us.
+    pub nodes: Vec<Option<N>>,
+}
+
+impl<N> Connection<N> {
+    /// A connection with nothing in it, for when GitHub gives us null in place of one.
+    pub fn empty() -> Self {
+        Connection {
+            page_info: PageInfo {
+                end_cursor: None,
+                has_next_page: false,
+            },
+            nodes: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PageInfo {
+    pub end_cursor: Option<String>,
+    pub has_next_page: bool,
+}
+
+/// Page through a connection, yielding its nodes as each page arrives; nodes that GitHub
+/// couldn't give us are `None`. The query is sent with the given variables, plus `$cursor`
+/// and `$first` for the page to fetch, and `connection` picks out the connection from each
+/// response. Paging starts after the cursor given, if any. Page sizes shrink if GitHub
+/// times out.
+pub fn paginate<'a, Res, N>(
+    api: &'a Api,
+    query: String,
+    variables: impl Fn() -> Variables + 'a,
+    connection: impl Fn(Res) -> Connection<N> + 'a,
+    after: Option<String>,
+) -> impl Stream<Item = Result<Option<N>, anyhow::Error>> + 'a
+where
+    Res: DeserializeOwned + 'a,
+    N: 'a,
+{
+    let state = PageState {
+        query,
+        variables,
+        connection,
+        cursor: after,
+        page_size: PageSize::default(),
+        finished: false,
+    };
+
+    let pages = stream::try_unfold(state, move |mut state| async move {
+        if state.finished {
+            return Ok(None);
+        }
+        loop {
+            let mut vars = (state.variables)();
+            vars.push("cursor", &state.cursor);
+            vars.push("first", state.page_size.get());
+
+            let res = match api.query_page(&state.query, vars, state.page_size).await {
+                Ok(res) => (state.connection)(res),
+                Err(e) if state.page_size.shrink_on_timeout(&e) => continue,
+                Err(e) => return Err(e),
+            };
+            state.page_size.grow();
+
+            state.cursor = res.page_info.end_cursor;
+            state.finished = !res.page_info.has_next_page || state.cursor.is_none();
+            return Ok(Some((res.nodes, state)));
+        }
+    });
+
+    pages
+        .map_ok(|nodes| stream::iter(nodes.into_iter().map(Ok)))
+        .try_flatten()
+}
+
+/// Where we've got to in paging through a connection.
+struct PageState<V, C> {
+    query: String,
+    variables: V,
+    connection: C,
+    cursor: Option<String>,
+    page_size: PageSize,
+    finished: bool,
+}
@@ -0,0 +1,110 @@
+use crate::api::contributions::{ContributionKind, QueryRepository};
+use crate::utils::DateTime;
+
+/// The reviews that a user has left on pull requests.
+pub struct PullRequestReviews;
+
+impl ContributionKind for PullRequestReviews {
+    const NAME: &'static str = "pull request reviews";
+    const QUERY_NAME: &'static str = "PullRequestReviewContributions";
+    const CONNECTION: &'static str =
+        "pull_request_review_contributions: pullRequestReviewContributions";
+    const NODE_FIELDS: &'static str = r#"
+        pull_request_review: pullRequestReview {
+            pull_request: pullRequest {
+                repository {
+                    name,
+                    owner { login }
+                },
+                title,
+                url,
+            },
+            state,
+            created_at: createdAt,
+            submitted_at: submittedAt,
+            body_text: bodyText,
+            comments { total_count: totalCount },
+            url,
+        }
+    "#;
+    const SCOPES: &'static [&'static str] = &["repo"];
+
+    type Node = PullRequestReviewContributionNode;
+    type Item = PullRequestReview;
+
+    fn item(node: PullRequestReviewContributionNode) -> Option<PullRequestReview> {
+        let review = node.pull_request_review?;
+        Some(PullRequestReview {
+            repository: review.pull_request.repository.name,
+            owner: review.pull_request.repository.owner.login,
+            pull_request_title: review.pull_request.title,
+            pull_request_url: review.pull_request.url,
+            state: review.state,
+            // Only pending reviews haven't been submitted, and those aren't contributions.
+            submitted_at: review.submitted_at.unwrap_or(review.created_at),
+            body_text: review.body_text,
+            comment_count: review.comments.total_count,
+            url: review.url,
+        })
+    }
+
+    fn url(item: &PullRequestReview) -> &str {
+        &item.url
+    }
+
+    fn created_at(item: &PullRequestReview) -> DateTime {
+        item.submitted_at
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct PullRequestReviewContributionNode {
+    pull_request_review: Option<PullRequestReviewInfo>,
+}
+
+#[derive(serde::Deserialize)]
+struct PullRequestReviewInfo {
+    pull_request: ReviewedPullRequest,
+    state: ReviewState,
+    created_at: DateTime,
+    submitted_at: Option<DateTime>,
+    body_text: String,
+    comments: ReviewComments,
+    url: String,
+}
+
+#[derive(serde::Deserialize)]
+struct ReviewedPullRequest {
+    repository: QueryRepository,
+    title: String,
+    url: String,
+}
+
+#[derive(serde::Deserialize)]
+struct ReviewComments {
+    total_count: usize,
+}
+
+/// What a reviewer concluded about a pull request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ReviewState {
+    Approved,
+    ChangesRequested,
+    Commented,
+    Dismissed,
+    Pending,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct PullRequestReview {
+    pub repository: String,
+    pub owner: String,
+    pub pull_request_title: String,
+    pub pull_request_url: String,
+    pub state: ReviewState,
+    pub submitted_at: DateTime,
+    pub body_text: String,
+    pub comment_count: usize,
+    pub url: String,
+}
@@ -0,0 +1,210 @@
+use reqwest::header::HeaderMap;
+use std::fmt;
+use std::io::{IsTerminal, Write};
+use std::sync::Mutex;
+use std::time::Duration;
+use time::OffsetDateTime;
+
+/// Keeps track of GitHub's GraphQL rate limit, so that we can wait for it to
+/// reset rather than failing when it runs out, and report on how much we used.
+#[derive(Debug, Default)]
+pub struct RateLimit {
+    state: Mutex<State>,
+    /// Held while waiting for the rate limit to reset, so that only one request prints a
+    /// countdown, and the rest wait for it to finish.
+    waiting: tokio::sync::Mutex<()>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    remaining: Option<u64>,
+    reset_at: Option<OffsetDateTime>,
+    points_used: u64,
+    requests: u64,
+}
+
+impl RateLimit {
+    /// Update our view of the rate limit from the headers of a response.
+    pub fn update(&self, headers: &HeaderMap) {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse::<i64>().ok())
+        };
+
+        let mut state = self.state.lock().unwrap();
+        state.requests += 1;
+        if let Some(remaining) = header("x-ratelimit-remaining") {
+            state.remaining = Some(remaining.max(0) as u64);
+        }
+        if let Some(reset) = header("x-ratelimit-reset") {
+            state.reset_at = OffsetDateTime::from_unix_timestamp(reset).ok();
+        }
+    }
+
+    /// Record the cost of a query, as reported in its `rateLimit` field.
+    pub fn record_cost(&self, cost: u64) {
+        self.state.lock().unwrap().points_used += cost;
+    }
+
+    /// Have we used up our budget, such that we need to wait for it to reset?
+    pub fn is_exhausted(&self) -> bool {
+        self.time_until_reset().is_some()
+    }
+
+    /// If the rate limit has been used up, wait until it resets, printing
+    /// a countdown to stderr while we do.
+    pub async fn wait_if_exhausted(&self) {
+        if self.time_until_reset().is_none() {
+            return;
+        }
+        // Whoever waited before us will have found that the limit has reset.
+        let _waiting = self.waiting.lock().await;
+        let Some(wait) = self.time_until_reset() else {
+            return;
+        };
+
+        let mut stderr = std::io::stderr();
+        let is_terminal = stderr.is_terminal();
+        let mut secs_left = wait.as_secs() + 1;
+
+        if !is_terminal {
+            eprintln!("GitHub rate limit exhausted; waiting {secs_left}s for it to reset");
+        }
+        while secs_left > 0 {
+            if is_terminal {
+                let _ = write!(
+                    stderr,
+                    "\rGitHub rate limit exhausted; resuming in {secs_left}s "
+                );
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            secs_left -= 1;
+        }
+        if is_terminal {
+            eprintln!();
+        }
+
+        // We'll learn the new limit from the next response.
+        self.state.lock().unwrap().remaining = None;
+    }
+
+    /// A summary of our rate limit usage so far.
+    pub fn summary(&self) -> RateLimitSummary {
+        let state = self.state.lock().unwrap();
+        RateLimitSummary {
+            points_used: state.points_used,
+            requests: state.requests,
+            remaining: state.remaining,
+            reset_at: state.reset_at,
+        }
+    }
+
+    fn time_until_reset(&self) -> Option<Duration> {
+        let state = self.state.lock().unwrap();
+        if state.remaining != Some(0) {
+            return None;
+        }
+
+        let wait = state.reset_at? - OffsetDateTime::now_utc();
+        wait.is_positive().then(|| wait.unsigned_abs())
+    }
+}
+
+/// How much of the rate limit we've used.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitSummary {
+    pub points_used: u64,
+    pub requests: u64,
+    pub remaining: Option<u64>,
+    pub reset_at: Option<OffsetDateTime>,
+}
+
+impl fmt::Display for RateLimitSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Used {} GitHub rate limit points across {} requests",
+            self.points_used, self.requests
+        )?;
+        if let Some(remaining) = self.remaining {
+            write!(f, " ({remaining} points remaining")?;
+            if let Some(reset_at) = self.reset_at {
+                let mins = (reset_at - OffsetDateTime::now_utc())
+                    .whole_minutes()
+                    .max(0);
+                write!(f, ", resetting in {mins} minutes")?;
+            }
+            write!(f, ")")?;
+        }
+        write!(f, ".")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn headers(remaining: i64, reset_in_secs: i64) -> HeaderMap {
+        let reset = OffsetDateTime::now_utc().unix_timestamp() + reset_in_secs;
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ratelimit-remaining", HeaderValue::from(remaining));
+        headers.insert("x-ratelimit-reset", HeaderValue::from(reset));
+        headers
+    }
+
+    #[test]
+    fn tracks_headers_and_costs() {
+        let rate_limit = RateLimit::default();
+        rate_limit.update(&HeaderMap::new());
+        rate_limit.update(&headers(4000, 600));
+        rate_limit.record_cost(1);
+        rate_limit.record_cost(2);
+
+        let summary = rate_limit.summary();
+        assert_eq!(summary.requests, 2);
+        assert_eq!(summary.points_used, 3);
+        assert_eq!(summary.remaining, Some(4000));
+        let summary = summary.to_string();
+        assert!(
+            summary.starts_with("Used 3 GitHub rate limit points across 2 requests (4000 points remaining, resetting in "),
+            "{summary}"
+        );
+    }
+
+    #[test]
+    fn is_exhausted_until_reset() {
+        let rate_limit = RateLimit::default();
+        assert!(!rate_limit.is_exhausted());
+
+        rate_limit.update(&headers(1, 600));
+        assert!(!rate_limit.is_exhausted());
+        rate_limit.update(&headers(0, 600));
+        assert!(rate_limit.is_exhausted());
+
+        // Once the reset time has passed, there's nothing to wait for.
+        rate_limit.update(&headers(0, -1));
+        assert!(!rate_limit.is_exhausted());
+    }
+
+    #[tokio::test]
+    async fn waits_for_reset_once() {
+        let rate_limit = RateLimit::default();
+        rate_limit.update(&headers(0, 1));
+        assert!(rate_limit.is_exhausted());
+
+        // Everyone waits, but only the first does so with a countdown, after which the
+        // limit is no longer known to be exhausted.
+        let start = std::time::Instant::now();
+        tokio::join!(
+            rate_limit.wait_if_exhausted(),
+            rate_limit.wait_if_exhausted(),
+            rate_limit.wait_if_exhausted(),
+        );
+        assert!(start.elapsed() >= Duration::from_secs(1));
+        assert!(!rate_limit.is_exhausted());
+        assert_eq!(rate_limit.summary().remaining, None);
+    }
+}
@@ -0,0 +1,168 @@
+use crate::utils::{self, DateTime};
+use anyhow::Context;
+use reqwest::StatusCode;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// The file in a recording directory which holds details about the recorded run.
+const RUN_FILE: &str = "run.json";
+
+/// Record API traffic to a directory, or replay previously recorded traffic from one.
+#[derive(Debug, Clone)]
+pub enum Recording {
+    Record(PathBuf),
+    Replay(PathBuf),
+}
+
+/// A request that we make to GitHub.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    pub method: String,
+    /// The path of the endpoint, relative to the API URL, so that recordings
+    /// can be replayed no matter which API URL is configured.
+    pub path: String,
+    pub body: Option<serde_json::Value>,
+}
+
+/// A response from GitHub, either received or replayed.
+#[derive(Debug, Clone)]
+pub struct RawResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: String,
+}
+
+/// Details about a recorded run, so that it can be replayed with the same dates.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedRun {
+    /// The time that the run happened at.
+    pub now: DateTime,
+    /// The UTC offset that dates were resolved in, in seconds.
+    pub offset_seconds: i32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Exchange {
+    request: RecordedRequest,
+    response: ExchangeResponse,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ExchangeResponse {
+    status: u16,
+    headers: BTreeMap<String, String>,
+    body: String,
+}
+
+impl Recording {
+    /// Save a request and the response to it.
+    pub async fn record(
+        &self,
+        request: &RecordedRequest,
+        response: &RawResponse,
+    ) -> Result<(), anyhow::Error> {
+        let Recording::Record(dir) = self else {
+            return Ok(());
+        };
+
+        let exchange = Exchange {
+            request: request.clone(),
+            response: ExchangeResponse {
+                status: response.status.as_u16(),
+                headers: response
+                    .headers
+                    .iter()
+                    .filter_map(|(k, v)| Some((k.to_string(), v.to_str().ok()?.to_owned())))
+                    .collect(),
+                body: response.body.clone(),
+            },
+        };
+
+        let path = dir.join(request_file_name(request));
+        let json = serde_json::to_vec_pretty(&exchange)?;
+        tokio::fs::create_dir_all(dir)
+            .await
+            .with_context(|| format!("Failed to create recording directory {}", dir.display()))?;
+        tokio::fs::write(&path, json)
+            .await
+            .with_context(|| format!("Failed to write recording {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Find the previously recorded response to a request, if we are replaying.
+    pub async fn replay(
+        &self,
+        request: &RecordedRequest,
+    ) -> Option<Result<RawResponse, anyhow::Error>> {
+        let Recording::Replay(dir) = self else {
+            return None;
+        };
+
+        let path = dir.join(request_file_name(request));
+        let load = async {
+            let json = tokio::fs::read(&path).await.with_context(|| {
+                format!(
+                    "No recorded response for {} {} in {}. Was it recorded using the \
+                    same arguments?",
+                    request.method,
+                    request.path,
+                    dir.display()
+                )
+            })?;
+            let exchange: Exchange = serde_json::from_slice(&json)
+                .with_context(|| format!("Failed to decode recording {}", path.display()))?;
+
+            let mut headers = HeaderMap::new();
+            for (k, v) in exchange.response.headers {
+                headers.insert(HeaderName::try_from(k)?, HeaderValue::try_from(v)?);
+            }
+            Ok(RawResponse {
+                status: StatusCode::from_u16(exchange.response.status)?,
+                headers,
+                body: exchange.response.body,
+            })
+        };
+        Some(load.await)
+    }
+
+    /// Save details about the current run, if we are recording.
+    pub fn record_run(&self, run: &RecordedRun) -> Result<(), anyhow::Error> {
+        let Recording::Record(dir) = self else {
+            return Ok(());
+        };
+
+        let path = dir.join(RUN_FILE);
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create recording directory {}", dir.display()))?;
+        std::fs::write(&path, serde_json::to_vec_pretty(run)?)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Load details about the recorded run, if we are replaying.
+    pub fn replay_run(&self) -> Result<Option<RecordedRun>, anyhow::Error> {
+        let Recording::Replay(dir) = self else {
+            return Ok(None);
+        };
+
+        let path = dir.join(RUN_FILE);
+        let json =
+            std::fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let run = serde_json::from_slice(&json)
+            .with_context(|| format!("Failed to decode {}", path.display()))?;
+        Ok(Some(run))
+    }
+}
+
+/// Requests are stored in files named after a hash of their contents.
+fn request_file_name(request: &RecordedRequest) -> String {
+    let body = request
+        .body
+        .as_ref()
+        .map(|b| b.to_string())
+        .unwrap_or_default();
+    let key = format!("{} {}\n{body}", request.method, request.path);
+    format!("{}.json", utils::sha256_hex(key.as_bytes()))
+}
@@ -0,0 +1,94 @@
+use std::time::Duration;
+
+/// How to retry requests which fail for transient reasons.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// The maximum number of times to try a request, including the first attempt.
+    pub max_attempts: u32,
+    /// The delay before the first retry. This doubles with each subsequent retry.
+    pub base_delay: Duration,
+    /// The longest that we'll wait between attempts, unless GitHub asks us to wait longer.
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 5,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// How long to wait after the given (1-based) attempt failed. If GitHub told us how long
+    /// to wait via `retry_after` then we wait for that long instead of backing off.
+    pub fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        // A little jitter stops concurrent requests from retrying in lockstep.
+        let jitter = |max: Duration| max.mul_f64(rand::random_range(0.0..1.0));
+
+        if let Some(retry_after) = retry_after {
+            return retry_after + jitter(Duration::from_secs(1));
+        }
+
+        let backoff = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+
+        // Wait for at least half of the backoff, and a random amount of the rest.
+        backoff / 2 + jitter(backoff / 2)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 10,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(10),
+        }
+    }
+
+    #[test]
+    fn backs_off_exponentially_with_jitter() {
+        for (attempt, backoff) in [(1, 1), (2, 2), (3, 4), (4, 8)] {
+            let backoff = Duration::from_secs(backoff);
+            for _ in 0..100 {
+                let delay = policy().delay(attempt, None);
+                assert!(
+                    delay >= backoff / 2 && delay <= backoff,
+                    "attempt {attempt}: {delay:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn caps_backoff_at_max_delay() {
+        for attempt in [5, 10, 100, u32::MAX] {
+            let delay = policy().delay(attempt, None);
+            assert!(
+                delay >= Duration::from_secs(5) && delay <= Duration::from_secs(10),
+                "attempt {attempt}: {delay:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn honours_retry_after() {
+        // Even beyond the longest that we'd usually wait.
+        let retry_after = Duration::from_secs(30);
+        for attempt in [1, 100] {
+            let delay = policy().delay(attempt, Some(retry_after));
+            assert!(
+                delay >= retry_after && delay <= retry_after + Duration::from_secs(1),
+                "attempt {attempt}: {delay:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,355 @@
+//! Checks our GraphQL queries against the parts of GitHub's schema that they use, which are
+//! vendored in `schema/github.graphql`. This catches queries that ask for fields which don't
+//! exist or pass arguments of the wrong type, and lets us build sample responses to check
+//! that our serde structs agree with the aliases that the queries use.
+
+use graphql_parser::query::{self as q, Definition, OperationDefinition, Selection, TypeCondition};
+use graphql_parser::schema::{self as s, Type, TypeDefinition};
+use serde_json::{Map, Value, json};
+use std::collections::HashMap;
+
+const SCHEMA: &str = include_str!("../../schema/github.graphql");
+
+/// Scalars which every GraphQL schema has without declaring them.
+const BUILTIN_SCALARS: &[&str] = &["String", "Int", "Float", "Boolean", "ID"];
+
+pub struct Schema {
+    types: HashMap<String, TypeDefinition<'static, String>>,
+}
+
+impl Schema {
+    pub fn github() -> Schema {
+        let document = graphql_parser::parse_schema::<String>(SCHEMA)
+            .expect("the vendored schema should parse")
+            .into_static();
+        let types = document
+            .definitions
+            .into_iter()
+            .filter_map(|definition| match definition {
+                s::Definition::TypeDefinition(ty) => Some((type_name(&ty).to_owned(), ty)),
+                _ => None,
+            })
+            .collect();
+        Schema { types }
+    }
+
+    /// Check a query against the schema, returning every problem found with it.
+    pub fn check(&self, query: &str) -> Result<(), Vec<String>> {
+        let operation = parse_operation(query).map_err(|e| vec![e])?;
+        let variables: HashMap<_, _> = operation
+            .variable_definitions
+            .iter()
+            .map(|v| (v.name.clone(), v))
+            .collect();
+
+        let mut problems = vec![];
+        for v in &operation.variable_definitions {
+            let name = named_type(&v.var_type);
+            if !self.is_input_type(name) {
+                problems.push(format!("${}: {name} isn't an input type", v.name));
+            }
+        }
+        self.check_selection(
+            "Query",
+            &operation.selection_set,
+            &variables,
+            "",
+            &mut problems,
+        );
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+
+    /// A response that GitHub could give to a query. Every field that the query selects is
+    /// given a value, unless `nulls` is true, in which case every nullable field below the
+    /// top level, other than lists, is null. Lists always have one item in them, so that the
+    /// fields selected on their items are there to check. The query should be valid; see
+    /// [`Schema::check`].
+    pub fn sample_response(&self, query: &str, nulls: bool) -> Value {
+        let operation = parse_operation(query).expect("the query should parse");
+        // Top-level fields are only null alongside errors, which fail the query before
+        // we'd try to decode it, so they're always given a value.
+        let mut response = Map::new();
+        for (key, field, selection) in self.selected_fields("Query", &operation.selection_set) {
+            let value = self.sample_non_null(&field.field_type, &selection.selection_set, nulls);
+            response.insert(key, value);
+        }
+        Value::Object(response)
+    }
+
+    fn check_selection(
+        &self,
+        type_name: &str,
+        selection_set: &q::SelectionSet<'static, String>,
+        variables: &HashMap<String, &q::VariableDefinition<'static, String>>,
+        path: &str,
+        problems: &mut Vec<String>,
+    ) {
+        for selection in &selection_set.items {
+            let field = match selection {
+                Selection::Field(field) => field,
+                Selection::InlineFragment(fragment) => {
+                    let on = match &fragment.type_condition {
+                        Some(TypeCondition::On(on)) => on.as_str(),
+                        None => type_name,
+                    };
+                    if self.fields(on).is_none() {
+                        problems.push(format!("{path}: can't select fields on {on}"));
+                    } else {
+                        let set = &fragment.selection_set;
+                        self.check_selection(on, set, variables, path, problems);
+                    }
+                    continue;
+                }
+                Selection::FragmentSpread(spread) => {
+                    problems.push(format!(
+                        "{path}: fragment spreads like ...{} aren't supported",
+                        spread.fragment_name
+                    ));
+                    continue;
+                }
+            };
+
+            let path = format!("{path}/{}", field.name);
+            if field.name == "__typename" {
+                continue;
+            }
+            let Some(definition) = self.field(type_name, &field.name) else {
+                problems.push(format!("{path}: {type_name} has no field {}", field.name));
+                continue;
+            };
+
+            for (name, value) in &field.arguments {
+                let Some(argument) = definition.arguments.iter().find(|a| &a.name == name) else {
+                    problems.push(format!("{path}: there's no argument {name}"));
+                    continue;
+                };
+                let q::Value::Variable(variable) = value else {
+                    continue;
+                };
+                match variables.get(variable) {
+                    None => problems.push(format!("{path}: ${variable} isn't declared")),
+                    Some(declared) => {
+                        let defaulted =
+                            declared.default_value.is_some() || argument.default_value.is_some();
+                        if !fits(&declared.var_type, &argument.value_type, defaulted) {
+                            problems.push(format!(
+                                "{path}: ${variable} is a {} but {name} takes a {}",
+                                declared.var_type, argument.value_type
+                            ));
+                        }
+                    }
+                }
+            }
+            for argument in &definition.arguments {
+                let required = matches!(argument.value_type, Type::NonNullType(_))
+                    && argument.default_value.is_none();
+                if required
+                    && !field
+                        .arguments
+                        .iter()
+                        .any(|(name, _)| name == &argument.name)
+                {
+                    problems.push(format!("{path}: argument {} is required", argument.name));
+                }
+            }
+
+            let field_type = named_type(&definition.field_type);
+            match (
+                self.fields(field_type),
+                field.selection_set.items.is_empty(),
+            ) {
+                (Some(_), true) => problems.push(format!("{path}: {field_type} needs fields")),
+                (None, false) => problems.push(format!("{path}: {field_type} has no fields")),
+                (Some(_), false) => {
+                    let set = &field.selection_set;
+                    self.check_selection(field_type, set, variables, &path, problems);
+                }
+                (None, true) => {}
+            }
+        }
+    }
+
+    /// A value for a field of the given type. Lists and their items are never null, since
+    /// GitHub always gives them, and we want to check the fields selected on the items.
+    fn sample(
+        &self,
+        ty: &Type<'static, String>,
+        selection_set: &q::SelectionSet<'static, String>,
+        nulls: bool,
+    ) -> Value {
+        match ty {
+            Type::NamedType(_) if nulls => Value::Null,
+            Type::NonNullType(ty) => self.sample_non_null(ty, selection_set, nulls),
+            ty => self.sample_non_null(ty, selection_set, nulls),
+        }
+    }
+
+    fn sample_non_null(
+        &self,
+        ty: &Type<'static, String>,
+        selection_set: &q::SelectionSet<'static, String>,
+        nulls: bool,
+    ) -> Value {
+        match ty {
+            Type::NonNullType(ty) => self.sample_non_null(ty, selection_set, nulls),
+            Type::ListType(ty) => json!([self.sample_non_null(ty, selection_set, nulls)]),
+            Type::NamedType(name) => self.sample_named(name, selection_set, nulls),
+        }
+    }
+
+    fn sample_named(
+        &self,
+        name: &str,
+        selection_set: &q::SelectionSet<'static, String>,
+        nulls: bool,
+    ) -> Value {
+        match self.types.get(name) {
+            Some(TypeDefinition::Object(_) | TypeDefinition::Interface(_)) => {
+                let mut object = Map::new();
+                for (key, field, selection) in self.selected_fields(name, selection_set) {
+                    let value = self.sample(&field.field_type, &selection.selection_set, nulls);
+                    object.insert(key, value);
+                }
+                Value::Object(object)
+            }
+            Some(TypeDefinition::Enum(e)) => json!(e.values[0].name),
+            Some(TypeDefinition::Scalar(_)) | None => match name {
+                "String" => json!("sample"),
+                "Int" => json!(1),
+                "Float" => json!(1.5),
+                "Boolean" => json!(true),
+                "ID" => json!("MDQ6VXNlcjE="),
+                "DateTime" | "GitTimestamp" => json!("2024-01-02T03:04:05Z"),
+                "GitObjectID" => json!("4b825dc642cb6eb9a060e54bf8d69288fbee4904"),
+                "URI" => json!("https://github.com/sample"),
+                _ => panic!("no sample value for the scalar {name}"),
+            },
+            Some(_) => panic!("no sample value for {name}"),
+        }
+    }
+
+    /// The fields selected on a type, including those in inline fragments, with the key
+    /// that each is found under in the response.
+    fn selected_fields<'a>(
+        &'a self,
+        type_name: &str,
+        selection_set: &'a q::SelectionSet<'static, String>,
+    ) -> Vec<(
+        String,
+        &'a s::Field<'static, String>,
+        &'a q::Field<'static, String>,
+    )> {
+        let mut fields = vec![];
+        for selection in &selection_set.items {
+            match selection {
+                Selection::Field(field) => {
+                    let key = field.alias.clone().unwrap_or_else(|| field.name.clone());
+                    if let Some(definition) = self.field(type_name, &field.name) {
+                        fields.push((key, definition, field));
+                    }
+                }
+                Selection::InlineFragment(fragment) => {
+                    let on = match &fragment.type_condition {
+                        Some(TypeCondition::On(on)) => on.as_str(),
+                        None => type_name,
+                    };
+                    fields.extend(self.selected_fields(on, &fragment.selection_set));
+                }
+                Selection::FragmentSpread(_) => {}
+            }
+        }
+        fields
+    }
+
+    fn fields(&self, type_name: &str) -> Option<&[s::Field<'static, String>]> {
+        match self.types.get(type_name)? {
+            TypeDefinition::Object(o) => Some(&o.fields),
+            TypeDefinition::Interface(i) => Some(&i.fields),
+            _ => None,
+        }
+    }
+
+    fn field(&self, type_name: &str, field_name: &str) -> Option<&s::Field<'static, String>> {
+        self.fields(type_name)?
+            .iter()
+            .find(|field| field.name == field_name)
+    }
+
+    fn is_input_type(&self, name: &str) -> bool {
+        BUILTIN_SCALARS.contains(&name)
+            || matches!(
+                self.types.get(name),
+                Some(
+                    TypeDefinition::Scalar(_)
+                        | TypeDefinition::Enum(_)
+                        | TypeDefinition::InputObject(_)
+                )
+            )
+    }
+}
+
+/// Decodes a sample response to a query, checking anything else about it that matters.
+pub type Decode<'a> = &'a dyn Fn(Value) -> serde_json::Result<()>;
+
+/// Check each query against GitHub's schema, and that sample responses to it can be decoded
+/// by the function given alongside it, whether or not GitHub gives us the nullable fields.
+pub fn check_queries(queries: &[(&str, Decode<'_>)]) {
+    let schema = Schema::github();
+    for (query, decode) in queries {
+        if let Err(problems) = schema.check(query) {
+            panic!("query is invalid:\n{}\n{query}", problems.join("\n"));
+        }
+        for nulls in [false, true] {
+            let response = schema.sample_response(query, nulls);
+            if let Err(e) = decode(response.clone()) {
+                panic!("response doesn't decode: {e}\n{response:#}\n{query}");
+            }
+        }
+    }
+}
+
+fn parse_operation(query: &str) -> Result<q::Query<'static, String>, String> {
+    let document = graphql_parser::parse_query::<String>(query)
+        .map_err(|e| format!("the query doesn't parse: {e}"))?
+        .into_static();
+    let mut operations = document.definitions.into_iter();
+    match (operations.next(), operations.next()) {
+        (Some(Definition::Operation(OperationDefinition::Query(query))), None) => Ok(query),
+        _ => Err("expected a single query".to_owned()),
+    }
+}
+
+/// Can a variable of the given type be passed to an argument of the other type?
+fn fits(variable: &Type<'_, String>, argument: &Type<'_, String>, defaulted: bool) -> bool {
+    match (variable, argument) {
+        (Type::NonNullType(v), Type::NonNullType(a)) => fits(v, a, false),
+        (Type::NonNullType(v), a) => fits(v, a, false),
+        (v, Type::NonNullType(a)) => defaulted && fits(v, a, false),
+        (Type::ListType(v), Type::ListType(a)) => fits(v, a, false),
+        (Type::NamedType(v), Type::NamedType(a)) => v == a,
+        _ => false,
+    }
+}
+
+fn named_type<'a>(ty: &'a Type<'static, String>) -> &'a str {
+    match ty {
+        Type::NamedType(name) => name,
+        Type::ListType(ty) | Type::NonNullType(ty) => named_type(ty),
+    }
+}
+
+fn type_name<'a>(ty: &'a TypeDefinition<'static, String>) -> &'a str {
+    match ty {
+        TypeDefinition::Scalar(t) => &t.name,
+        TypeDefinition::Object(t) => &t.name,
+        TypeDefinition::Interface(t) => &t.name,
+        TypeDefinition::Union(t) => &t.name,
+        TypeDefinition::Enum(t) => &t.name,
+        TypeDefinition::InputObject(t) => &t.name,
+    }
+}
@@ -0,0 +1,217 @@
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// This represents variables you can pass to a GraphQL query. Variables are checked
+/// against those that the query declares before it's sent.
+#[derive(Debug, Default)]
+pub struct Variables {
+    values: Map<String, Value>,
+    /// The first variable which couldn't be serialized, if any. This is reported when
+    /// the variables are built, so that pushing them doesn't need to fail.
+    error: Option<VariablesError>,
+}
+
+/// A problem with the variables given to a query.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum VariablesError {
+    #[error("variable ${0} could not be serialized: {1}")]
+    Serialize(String, String),
+    #[error("variable ${0} is required by the query but wasn't given")]
+    Missing(String),
+    #[error("variable ${0} was given but isn't declared by the query")]
+    Unknown(String),
+    #[error("variable ${0} has the non-null type {1} but was given null")]
+    Null(String, String),
+    #[error("couldn't parse the variables declared by the query: {0}")]
+    BadDeclaration(String),
+}
+
+/// A variable declared by a query, like `$first:Int!` or `$cursor:String`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Declaration {
+    name: String,
+    ty: String,
+    has_default: bool,
+}
+
+impl Variables {
+    pub fn new() -> Self {
+        Variables::default()
+    }
+
+    pub fn push<T: Serialize>(&mut self, name: &str, value: T) {
+        match serde_json::to_value(value) {
+            Ok(value) => {
+                self.values.insert(name.to_owned(), value);
+            }
+            Err(e) => {
+                self.error
+                    .get_or_insert(VariablesError::Serialize(name.to_owned(), e.to_string()));
+            }
+        }
+    }
+
+    /// Check these variables against those declared by the query, and build them into the
+    /// JSON object to send alongside it, or `None` if there are no variables to send.
+    pub fn build(self, query: &str) -> Result<Option<Map<String, Value>>, VariablesError> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+
+        let declarations = declarations(query)?;
+        if let Some(name) = self
+            .values
+            .keys()
+            .find(|name| !declarations.iter().any(|d| &d.name == *name))
+        {
+            return Err(VariablesError::Unknown(name.clone()));
+        }
+
+        for declaration in &declarations {
+            let is_non_null = declaration.ty.ends_with('!');
+            match self.values.get(&declaration.name) {
+                None if is_non_null && !declaration.has_default => {
+                    return Err(VariablesError::Missing(declaration.name.clone()));
+                }
+                Some(Value::Null) if is_non_null => {
+                    return Err(VariablesError::Null(
+                        declaration.name.clone(),
+                        declaration.ty.clone(),
+                    ));
+                }
+                _ => {}
+            }
+        }
+
+        Ok((!self.values.is_empty()).then_some(self.values))
+    }
+}
+
+/// The variables that a query like `query Name($user:String!, $first:Int = 10) { ... }`
+/// declares. Queries without a list of variables declare none.
+fn declarations(query: &str) -> Result<Vec<Declaration>, VariablesError> {
+    // Variables are declared between parens before the selection set starts.
+    let header = query.split('{').next().unwrap_or_default();
+    let Some((_, rest)) = header.split_once('(') else {
+        return Ok(vec![]);
+    };
+    let Some((list, _)) = rest.rsplit_once(')') else {
+        return Err(VariablesError::BadDeclaration(header.trim().to_owned()));
+    };
+
+    split_top_level(list)
+        .into_iter()
+        .filter(|decl| !decl.trim().is_empty())
+        .map(|decl| {
+            let bad = || VariablesError::BadDeclaration(decl.trim().to_owned());
+            let (name, ty) = decl.split_once(':').ok_or_else(bad)?;
+            let name = name.trim().strip_prefix('$').ok_or_else(bad)?;
+            let (ty, default) = match ty.split_once('=') {
+                Some((ty, default)) => (ty, Some(default)),
+                None => (ty, None),
+            };
+            let ty = ty.trim();
+            if name.is_empty() || ty.is_empty() {
+                return Err(bad());
+            }
+            Ok(Declaration {
+                name: name.to_owned(),
+                ty: ty.to_owned(),
+                has_default: default.is_some(),
+            })
+        })
+        .collect()
+}
+
+/// Split a variable list on the commas that aren't inside a list type or default value.
+fn split_top_level(list: &str) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut depth = 0i32;
+    let mut start = 0;
+    for (idx, c) in list.char_indices() {
+        match c {
+            '[' | '{' => depth += 1,
+            ']' | '}' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&list[start..idx]);
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&list[start..]);
+    parts
+}
+
+#[macro_export]
+macro_rules! variables {
+    ($($key:literal : $val:expr), *) => {{
+        // May be unused if empty; no params.
+        #[allow(unused_mut)]
+        let mut params = $crate::api::variables::Variables::new();
+        $(
+            params.push($key, $val);
+        )*
+        params
+    }}
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    const QUERY: &str = r#"
+        query Test($user:String!, $cursor:String, $first:Int! = 100, $ids: [ID!]! = ["a", "b"]) {
+            user(login:$user) { login }
+        }
+    "#;
+
+    #[test]
+    fn variables_build_json_object() {
+        let cursor: Option<String> = None;
+        let vars = variables!(
+            "user": "octocat",
+            "first": 100,
+            "cursor": cursor,
+            "ids": ["a \"b\""]
+        );
+
+        assert_eq!(
+            Value::Object(vars.build(QUERY).unwrap().unwrap()),
+            json!({ "user": "octocat", "first": 100, "cursor": null, "ids": ["a \"b\""] })
+        );
+    }
+
+    #[test]
+    fn no_variables_build_to_nothing() {
+        assert_eq!(
+            variables!().build("query Test { viewer { login } }"),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn variables_are_checked_against_the_query() {
+        assert_eq!(
+            variables!("first": 10).build(QUERY),
+            Err(VariablesError::Missing("user".to_owned()))
+        );
+        assert_eq!(
+            variables!("user": "octocat", "after": "abc").build(QUERY),
+            Err(VariablesError::Unknown("after".to_owned()))
+        );
+        assert_eq!(
+            variables!("user": Option::<String>::None).build(QUERY),
+            Err(VariablesError::Null(
+                "user".to_owned(),
+                "String!".to_owned()
+            ))
+        );
+        assert!(
+            variables!("user": "octocat")
+                .build("query Broken($user) { viewer { login } }")
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,204 @@
+use anyhow::Context;
+use std::fmt;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// A token that was given to us explicitly, via one of the command line options.
+#[derive(Debug, Clone)]
+pub enum GivenToken {
+    /// `--gh-token`
+    Arg(String),
+    /// `--gh-token-file`
+    File(PathBuf),
+    /// `--gh-token-stdin`
+    Stdin,
+}
+
+/// Where we found a token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenSource {
+    Given,
+    Env,
+    GhCli,
+    GitCredential,
+}
+
+impl fmt::Display for TokenSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenSource::Given => write!(f, "the command line"),
+            TokenSource::Env => write!(f, "the GITHUB_TOKEN env var"),
+            TokenSource::GhCli => write!(f, "the GitHub CLI's hosts.yml"),
+            TokenSource::GitCredential => write!(f, "git credential fill"),
+        }
+    }
+}
+
+/// Find a GitHub token for the given host (like `github.com`). We look in each of
+/// these places in turn, and use the first token that we find:
+///
+/// 1. `--gh-token`, `--gh-token-file` or `--gh-token-stdin`, if given.
+/// 2. The `GITHUB_TOKEN` env var.
+/// 3. The GitHub CLI's `hosts.yml`, if it has a token for the host.
+/// 4. `git credential fill` for the host.
+pub fn find_token(
+    given: Option<GivenToken>,
+    host: &str,
+) -> Result<Option<(String, TokenSource)>, anyhow::Error> {
+    if let Some(given) = given {
+        let token = match given {
+            GivenToken::Arg(token) => token,
+            GivenToken::File(path) => std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read token from {}", path.display()))?,
+            GivenToken::Stdin => {
+                let mut token = String::new();
+                std::io::stdin()
+                    .read_to_string(&mut token)
+                    .context("Failed to read token from stdin")?;
+                token
+            }
+        };
+        let token = token.trim();
+        if token.is_empty() {
+            return Err(anyhow::anyhow!("The GitHub token given is empty"));
+        }
+        return Ok(Some((token.to_owned(), TokenSource::Given)));
+    }
+
+    if let Ok(token) = std::env::var("GITHUB_TOKEN")
+        && !token.trim().is_empty()
+    {
+        return Ok(Some((token.trim().to_owned(), TokenSource::Env)));
+    }
+
+    if let Some(token) = gh_cli_hosts_path()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|hosts| gh_cli_token(&hosts, host))
+    {
+        return Ok(Some((token, TokenSource::GhCli)));
+    }
+
+    if let Some(token) = git_credential_token(host) {
+        return Ok(Some((token, TokenSource::GitCredential)));
+    }
+
+    Ok(None)
+}
+
+/// Where the GitHub CLI keeps its `hosts.yml`.
+fn gh_cli_hosts_path() -> Option<PathBuf> {
+    let non_empty = |var| std::env::var_os(var).filter(|dir| !dir.is_empty());
+    let config_dir = non_empty("GH_CONFIG_DIR")
+        .map(PathBuf::from)
+        .or_else(|| non_empty("XDG_CONFIG_HOME").map(|dir| Path::new(&dir).join("gh")))
+        .or_else(|| non_empty("HOME").map(|home| Path::new(&home).join(".config").join("gh")))?;
+    Some(config_dir.join("hosts.yml"))
+}
+
+/// Find the token for a host in the GitHub CLI's `hosts.yml`, which looks like:
+///
+/// ```yaml
+/// github.com:
+///     user: octocat
+///     oauth_token: gho_abc123
+///     git_protocol: https
+/// ```
+///
+/// Newer versions of the CLI keep the token in the system keyring instead, in which
+/// case there's nothing for us to find.
+fn gh_cli_token(hosts: &str, host: &str) -> Option<String> {
+    let mut in_host = false;
+    let mut indent = None;
+    for line in hosts.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        let line_indent = line.len() - trimmed.len();
+        if line_indent == 0 {
+            in_host = unquote(trimmed.trim_end().trim_end_matches(':')) == host;
+            indent = None;
+            continue;
+        }
+        if !in_host {
+            continue;
+        }
+
+        // Only look at keys directly under the host, and not those of any nested users.
+        let indent = *indent.get_or_insert(line_indent);
+        if line_indent == indent
+            && let Some((key, value)) = trimmed.split_once(':')
+            && key.trim() == "oauth_token"
+        {
+            let token = unquote(value.trim());
+            return (!token.is_empty()).then(|| token.to_owned());
+        }
+    }
+    None
+}
+
+fn unquote(s: &str) -> &str {
+    s.trim_matches(|c| c == '"' || c == '\'')
+}
+
+/// Ask git for the password it has stored for the host, if any. Git is told not to
+/// prompt for anything, so this only finds credentials that it already has.
+fn git_credential_token(host: &str) -> Option<String> {
+    let mut child = Command::new("git")
+        .args(["credential", "fill"])
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .env("GCM_INTERACTIVE", "never")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+
+    let request = format!("protocol=https\nhost={host}\n\n");
+    child.stdin.take()?.write_all(request.as_bytes()).ok()?;
+    let output = child.wait_with_output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    String::from_utf8(output.stdout)
+        .ok()?
+        .lines()
+        .find_map(|line| line.strip_prefix("password="))
+        .map(|password| password.trim().to_owned())
+        .filter(|password| !password.is_empty())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn finds_tokens_in_gh_cli_hosts() {
+        let hosts = r#"
+github.com:
+    users:
+        octocat:
+            oauth_token: gho_nested
+    user: octocat
+    oauth_token: gho_github
+    git_protocol: https
+"ghe.example.com":
+    oauth_token: "gho_ghe"
+keyring.example.com:
+    user: octocat
+"#;
+        assert_eq!(
+            gh_cli_token(hosts, "github.com").as_deref(),
+            Some("gho_github")
+        );
+        assert_eq!(
+            gh_cli_token(hosts, "ghe.example.com").as_deref(),
+            Some("gho_ghe")
+        );
+        assert_eq!(gh_cli_token(hosts, "keyring.example.com"), None);
+        assert_eq!(gh_cli_token(hosts, "unknown.example.com"), None);
+    }
+}
@@ -0,0 +1,427 @@
+use crate::utils::DateTime;
+use std::str::FromStr;
+use time::{Date, Duration, Month, OffsetDateTime, Time, UtcOffset};
+
+/// A point or period in time as given on the command line. This can be:
+///
+/// - An ISO DateTime, for example '2025-06-01T00:00:00Z'.
+/// - A date, month, quarter, half or year, for example '2025-06-01', '2025-06',
+///   '2025-Q2', '2025H1' or '2025'.
+/// - A named period relative to today: 'today', 'yesterday', 'this-week',
+///   'last-week', and likewise for 'month', 'quarter' and 'year'.
+/// - A duration ago, for example '12h', '7d' or '2w'.
+///
+/// Periods are resolved using some UTC offset, and have a start and an end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateSpec {
+    Instant(DateTime),
+    Ago(Duration),
+    Period(Period),
+    Relative(RelativePeriod),
+}
+
+/// A fixed period of time, independent of when it is resolved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Period {
+    Day(Date),
+    /// A week, starting on the given Monday.
+    Week(Date),
+    Month(i32, Month),
+    Quarter(i32, u8),
+    Half(i32, u8),
+    Year(i32),
+}
+
+/// A period of time which depends on the current date.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelativePeriod {
+    Today,
+    Yesterday,
+    ThisWeek,
+    LastWeek,
+    ThisMonth,
+    LastMonth,
+    ThisQuarter,
+    LastQuarter,
+    ThisYear,
+    LastYear,
+}
+
+impl DateSpec {
+    /// The point in time that this begins at.
+    pub fn start(&self, now: DateTime, offset: UtcOffset) -> DateTime {
+        self.resolve(now, offset).0
+    }
+
+    /// The point in time that this ends at. For periods, this is the
+    /// start of the following period.
+    pub fn end(&self, now: DateTime, offset: UtcOffset) -> DateTime {
+        self.resolve(now, offset).1
+    }
+
+    fn resolve(&self, now: DateTime, offset: UtcOffset) -> (DateTime, DateTime) {
+        let period = match self {
+            DateSpec::Instant(dt) => return (*dt, *dt),
+            DateSpec::Ago(duration) => {
+                // Durations are limited to `MAX_AGO` when parsed, so this can only fail for
+                // a `now` in the distant past.
+                let dt = DateTime(now.0.checked_sub(*duration).unwrap_or(now.0));
+                return (dt, dt);
+            }
+            DateSpec::Period(period) => *period,
+            DateSpec::Relative(relative) => relative.resolve(now.0.to_offset(offset).date()),
+        };
+
+        let (start, end) = period.dates();
+        (at_midnight(start, offset), at_midnight(end, offset))
+    }
+}
+
+impl Period {
+    /// The first day of this period, and the first day after it.
+    fn dates(&self) -> (Date, Date) {
+        match *self {
+            Period::Day(date) => (date, date.next_day().unwrap_or(date)),
+            Period::Week(monday) => (monday, monday + Duration::weeks(1)),
+            Period::Month(year, month) => (first_of(year, month), first_of_next(year, month)),
+            Period::Quarter(year, quarter) => {
+                let first = month_from_index(3 * (quarter - 1) + 1);
+                let last = month_from_index(3 * quarter);
+                (first_of(year, first), first_of_next(year, last))
+            }
+            Period::Half(year, half) => {
+                let first = month_from_index(6 * (half - 1) + 1);
+                let last = month_from_index(6 * half);
+                (first_of(year, first), first_of_next(year, last))
+            }
+            Period::Year(year) => (
+                first_of(year, Month::January),
+                first_of(year + 1, Month::January),
+            ),
+        }
+    }
+}
+
+impl RelativePeriod {
+    /// Work out which fixed period this refers to, given today's date.
+    fn resolve(&self, today: Date) -> Period {
+        let year = today.year();
+        let month = today.month();
+        let quarter = (month as u8 - 1) / 3 + 1;
+        let this_monday = today - Duration::days(today.weekday().number_days_from_monday() as i64);
+
+        match self {
+            RelativePeriod::Today => Period::Day(today),
+            RelativePeriod::Yesterday => Period::Day(today.previous_day().unwrap_or(today)),
+            RelativePeriod::ThisWeek => Period::Week(this_monday),
+            RelativePeriod::LastWeek => Period::Week(this_monday - Duration::weeks(1)),
+            RelativePeriod::ThisMonth => Period::Month(year, month),
+            RelativePeriod::LastMonth => {
+                let prev_year = if month == Month::January {
+                    year - 1
+                } else {
+                    year
+                };
+                Period::Month(prev_year, month.previous())
+            }
+            RelativePeriod::ThisQuarter => Period::Quarter(year, quarter),
+            RelativePeriod::LastQuarter if quarter == 1 => Period::Quarter(year - 1, 4),
+            RelativePeriod::LastQuarter => Period::Quarter(year, quarter - 1),
+            RelativePeriod::ThisYear => Period::Year(year),
+            RelativePeriod::LastYear => Period::Year(year - 1),
+        }
+    }
+}
+
+impl FromStr for DateSpec {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let lower = s.to_ascii_lowercase();
+
+        if let Ok(dt) = DateTime::from_str(s) {
+            return Ok(DateSpec::Instant(dt));
+        }
+        if let Some(relative) = parse_relative_period(&lower) {
+            return Ok(DateSpec::Relative(relative));
+        }
+        if let Some(duration) = parse_ago(&lower) {
+            return Ok(DateSpec::Ago(duration));
+        }
+        if let Some(period) = parse_period(&lower) {
+            return Ok(DateSpec::Period(period));
+        }
+
+        Err(anyhow::anyhow!(
+            "Could not parse {s:?} as a date. Expected an ISO DateTime like \
+            '2025-06-01T00:00:00Z', a date or period like '2025-06-01', '2025-06', \
+            '2025-Q2', '2025H1' or '2025', a named period like 'yesterday' or \
+            'last-month', or a duration ago like '7d' or '2w'"
+        ))
+    }
+}
+
+/// Parse a UTC offset like 'UTC', 'Z', '+02:00', '-0530' or '+2'.
+pub fn parse_offset(s: &str) -> Result<UtcOffset, anyhow::Error> {
+    let err = || anyhow::anyhow!("Could not parse {s:?} as a UTC offset like '+02:00' or 'UTC'");
+
+    let s = s.trim();
+    if s.eq_ignore_ascii_case("utc") || s.eq_ignore_ascii_case("z") {
+        return Ok(UtcOffset::UTC);
+    }
+
+    let (sign, rest) = match s.split_at_checked(1) {
+        Some(("+", rest)) => (1, rest),
+        Some(("-", rest)) => (-1, rest),
+        _ => return Err(err()),
+    };
+    let (hours, minutes) = match rest.split_once(':') {
+        Some((h, m)) => (h, m),
+        None if rest.len() > 2 => rest.split_at(rest.len() - 2),
+        None => (rest, "0"),
+    };
+    let hours: i8 = hours.parse().map_err(|_| err())?;
+    let minutes: i8 = minutes.parse().map_err(|_| err())?;
+
+    UtcOffset::from_hms(sign * hours, sign * minutes, 0).map_err(|_| err())
+}
+
+fn parse_relative_period(s: &str) -> Option<RelativePeriod> {
+    let relative = match s {
+        "today" => RelativePeriod::Today,
+        "yesterday" => RelativePeriod::Yesterday,
+        "this-week" => RelativePeriod::ThisWeek,
+        "last-week" => RelativePeriod::LastWeek,
+        "this-month" => RelativePeriod::ThisMonth,
+        "last-month" => RelativePeriod::LastMonth,
+        "this-quarter" => RelativePeriod::ThisQuarter,
+        "last-quarter" => RelativePeriod::LastQuarter,
+        "this-year" => RelativePeriod::ThisYear,
+        "last-year" => RelativePeriod::LastYear,
+        _ => return None,
+    };
+    Some(relative)
+}
+
+/// The longest duration ago that we accept, which is plenty to cover all of GitHub's
+/// history while keeping well clear of the earliest date that can be represented.
+const MAX_AGO: Duration = Duration::days(1000 * 366);
+/// The last year that periods can be in. Periods end at the start of whatever follows them,
+/// and nothing after the year 9999 can be represented.
+const MAX_YEAR: i32 = 9998;
+
+/// Parse a duration like '30s', '15m', '12h', '7d' or '2w'.
+pub fn parse_duration(s: &str) -> Result<std::time::Duration, anyhow::Error> {
+    let err = || anyhow::anyhow!("Could not parse {s:?} as a duration like '15m', '12h' or '7d'");
+
+    let (n, unit) = split_number(s.trim()).ok_or_else(err)?;
+    let seconds_per_unit = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(err()),
+    };
+    let seconds = n.checked_mul(seconds_per_unit).ok_or_else(err)?;
+    Duration::seconds(seconds).try_into().map_err(|_| err())
+}
+
+fn parse_ago(s: &str) -> Option<Duration> {
+    let (n, unit) = split_number(s)?;
+    let seconds_per_unit = match unit {
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return None,
+    };
+    let duration = Duration::seconds(n.checked_mul(seconds_per_unit)?);
+    (duration <= MAX_AGO).then_some(duration)
+}
+
+/// Split something like '12h' into the number and the unit following it. Numbers too
+/// large for an `i64` aren't accepted.
+fn split_number(s: &str) -> Option<(i64, &str)> {
+    let unit_idx = s.find(|c: char| !c.is_ascii_digit())?;
+    let (n, unit) = s.split_at(unit_idx);
+    Some((n.parse().ok()?, unit))
+}
+
+fn parse_period(s: &str) -> Option<Period> {
+    let (year, rest) = s.split_at_checked(4)?;
+    let year: i32 = year.parse().ok()?;
+    if year > MAX_YEAR {
+        return None;
+    }
+    if rest.is_empty() {
+        return Some(Period::Year(year));
+    }
+
+    // Quarters and halves may leave out the '-', but months and days need it.
+    let (dashed, rest) = match rest.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, rest),
+    };
+    if let Some(quarter) = rest.strip_prefix('q') {
+        let quarter: u8 = quarter.parse().ok()?;
+        return (1..=4)
+            .contains(&quarter)
+            .then_some(Period::Quarter(year, quarter));
+    }
+    if let Some(half) = rest.strip_prefix('h') {
+        let half: u8 = half.parse().ok()?;
+        return (1..=2).contains(&half).then_some(Period::Half(year, half));
+    }
+    if !dashed {
+        return None;
+    }
+
+    match rest.split_once('-') {
+        None => {
+            let month = Month::try_from(rest.parse::<u8>().ok()?).ok()?;
+            Some(Period::Month(year, month))
+        }
+        Some((month, day)) => {
+            let month = Month::try_from(month.parse::<u8>().ok()?).ok()?;
+            let date = Date::from_calendar_date(year, month, day.parse().ok()?).ok()?;
+            Some(Period::Day(date))
+        }
+    }
+}
+
+fn at_midnight(date: Date, offset: UtcOffset) -> DateTime {
+    DateTime(OffsetDateTime::new_in_offset(date, Time::MIDNIGHT, offset))
+}
+
+fn first_of(year: i32, month: Month) -> Date {
+    Date::from_calendar_date(year, month, 1).expect("the 1st of every month is valid")
+}
+
+fn first_of_next(year: i32, month: Month) -> Date {
+    if month == Month::December {
+        first_of(year + 1, Month::January)
+    } else {
+        first_of(year, month.next())
+    }
+}
+
+fn month_from_index(n: u8) -> Month {
+    Month::try_from(n).expect("month index should be between 1 and 12")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn dt(s: &str) -> DateTime {
+        s.parse().unwrap()
+    }
+
+    fn range(spec: &str, now: &str, offset: &str) -> (DateTime, DateTime) {
+        let spec: DateSpec = spec.parse().unwrap();
+        let offset = parse_offset(offset).unwrap();
+        (spec.start(dt(now), offset), spec.end(dt(now), offset))
+    }
+
+    #[test]
+    fn fixed_periods() {
+        let now = "2025-02-12T10:00:00Z";
+        let cases = [
+            ("2025-06-01", "2025-06-01T00:00Z", "2025-06-02T00:00Z"),
+            ("2025-06", "2025-06-01T00:00Z", "2025-07-01T00:00Z"),
+            ("2025-Q2", "2025-04-01T00:00Z", "2025-07-01T00:00Z"),
+            ("2025q4", "2025-10-01T00:00Z", "2026-01-01T00:00Z"),
+            ("2025H1", "2025-01-01T00:00Z", "2025-07-01T00:00Z"),
+            ("2025-h2", "2025-07-01T00:00Z", "2026-01-01T00:00Z"),
+            ("2025", "2025-01-01T00:00Z", "2026-01-01T00:00Z"),
+        ];
+        for (spec, start, end) in cases {
+            assert_eq!(range(spec, now, "UTC"), (dt(start), dt(end)), "{spec}");
+        }
+    }
+
+    #[test]
+    fn relative_periods() {
+        // A Wednesday.
+        let now = "2025-01-15T10:00:00Z";
+        let cases = [
+            ("today", "2025-01-15T00:00Z", "2025-01-16T00:00Z"),
+            ("yesterday", "2025-01-14T00:00Z", "2025-01-15T00:00Z"),
+            ("this-week", "2025-01-13T00:00Z", "2025-01-20T00:00Z"),
+            ("last-week", "2025-01-06T00:00Z", "2025-01-13T00:00Z"),
+            ("last-month", "2024-12-01T00:00Z", "2025-01-01T00:00Z"),
+            ("last-quarter", "2024-10-01T00:00Z", "2025-01-01T00:00Z"),
+            ("last-year", "2024-01-01T00:00Z", "2025-01-01T00:00Z"),
+            ("7d", "2025-01-08T10:00Z", "2025-01-08T10:00Z"),
+            ("2w", "2025-01-01T10:00Z", "2025-01-01T10:00Z"),
+            (
+                "2025-01-01T00:00Z",
+                "2025-01-01T00:00Z",
+                "2025-01-01T00:00Z",
+            ),
+        ];
+        for (spec, start, end) in cases {
+            assert_eq!(range(spec, now, "UTC"), (dt(start), dt(end)), "{spec}");
+        }
+    }
+
+    #[test]
+    fn periods_resolve_in_offset() {
+        // It's already the 16th in +12:00.
+        let (start, end) = range("today", "2025-01-15T20:00:00Z", "+12:00");
+        assert_eq!(start, dt("2025-01-16T00:00+12:00"));
+        assert_eq!(end, dt("2025-01-17T00:00+12:00"));
+    }
+
+    #[test]
+    fn bad_specs_are_rejected() {
+        for spec in [
+            "",
+            "2025-13",
+            "2025-Q5",
+            "2025H3",
+            "7y",
+            "next-week",
+            "2025-02-30",
+            "20251",
+            "202506",
+            "2025-",
+            "2025-q",
+            "9999",
+            "9999-Q4",
+            "9999-12",
+            "9999-12-31",
+        ] {
+            assert!(spec.parse::<DateSpec>().is_err(), "{spec}");
+        }
+    }
+
+    #[test]
+    fn huge_durations_are_rejected() {
+        for spec in ["9999999999999w", "99999999999999999999d", "400000w"] {
+            assert!(spec.parse::<DateSpec>().is_err(), "{spec}");
+        }
+        // Up to a thousand years ago is fine, though.
+        let (start, _) = range("52000w", "2025-01-15T10:00:00Z", "UTC");
+        assert_eq!(start.0.year(), 1028);
+
+        for duration in ["99999999999999w", "99999999999999999999s", "-1h", "7y"] {
+            assert!(parse_duration(duration).is_err(), "{duration}");
+        }
+        assert_eq!(
+            parse_duration("2w").unwrap(),
+            std::time::Duration::from_secs(14 * 24 * 60 * 60)
+        );
+    }
+
+    #[test]
+    fn offsets() {
+        let offset = |s| parse_offset(s).unwrap().whole_seconds();
+        assert_eq!(offset("UTC"), 0);
+        assert_eq!(offset("Z"), 0);
+        assert_eq!(offset("+02:00"), 2 * 3600);
+        assert_eq!(offset("+2"), 2 * 3600);
+        assert_eq!(offset("-0530"), -(5 * 3600 + 30 * 60));
+        assert!(parse_offset("Europe/London").is_err());
+    }
+}
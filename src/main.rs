@@ -1,54 +1,365 @@
 mod api;
+mod credentials;
+mod dates;
 mod utils;
 
-use api::client::Api;
-use clap::Parser;
+use anyhow::Context;
+use api::auth::{Auth, GitHubApp};
+use api::cache::Cache;
+use api::client::{Api, ApiError, ApiOptions, ApiUrl};
+use api::comments;
+use api::commits::{self, RepositoryCommits};
+use api::contributions::{self, Batched, ContributionKind, FirstPages, Restricted};
+use api::discussions;
+use api::issues::Issues;
+use api::pull_request_reviews::{PullRequestReviews, ReviewState};
+use api::pull_requests::PullRequests;
+use api::recording::{RecordedRun, Recording};
+use api::repositories::Repositories;
+use api::retry::RetryPolicy;
+use clap::{Parser, Subcommand};
+use credentials::{GivenToken, TokenSource};
+use dates::DateSpec;
+use futures::{TryStreamExt, future, stream};
+use std::path::PathBuf;
+use std::time::Duration;
+use time::UtcOffset;
 use utils::DateTime;
 
+/// The kinds of contribution whose first pages are fetched together, in a single query per
+/// window of time; more queries are only needed for those with more pages.
+const BATCHED_KINDS: [Batched; 4] = [
+    Batched::of::<PullRequests>(),
+    Batched::of::<PullRequestReviews>(),
+    Batched::of::<Issues>(),
+    Batched::of::<Repositories>(),
+];
+
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(
+    author,
+    version,
+    about,
+    long_about = None,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 struct Cli {
-    /// An ISO DateTime, for example '2025-06-01T00:00:00Z'.
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// When to summarize activity from. This can be an ISO DateTime like
+    /// '2025-06-01T00:00:00Z', a date like '2025-06-01', a period like '2025-06',
+    /// '2025-Q2', '2025H1', '2025', 'yesterday', 'this-week' or 'last-month' (in
+    /// which case the start of the period is used), or a duration ago like '7d' or '2w'.
+    #[arg(long, required = true)]
+    from: Option<DateSpec>,
+
+    /// When to summarize activity up to. This accepts the same values as --from,
+    /// but uses the end of any period given, so '--from 2025-Q2 --to 2025-Q2'
+    /// covers the whole of that quarter. Defaults to now.
     #[arg(long)]
-    from: DateTime,
+    to: Option<DateSpec>,
+
+    /// The UTC offset to resolve dates and periods in, for example '+02:00' or 'UTC'.
+    /// Defaults to the local offset.
+    #[arg(long, value_parser = dates::parse_offset)]
+    timezone: Option<UtcOffset>,
 
-    /// A GitHub token to enable access to the APIs. This can also
-    /// be provided via the env var GITHUB_TOKEN.
+    /// A GitHub token to enable access to the APIs. Prefer --gh-token-file or
+    /// --gh-token-stdin, since arguments are visible to other processes.
+    ///
+    /// If no token is given, we look in the env var GITHUB_TOKEN, then the
+    /// GitHub CLI's hosts.yml, then ask 'git credential fill' for one.
     ///
     /// The token should have read access to discussions, issues,
     /// metadata, and pull requests.
-    #[arg(long)]
+    #[arg(long, conflicts_with_all = ["gh_token_file", "gh_token_stdin"])]
     gh_token: Option<String>,
 
+    /// Read the GitHub token from the given file.
+    #[arg(long, conflicts_with = "gh_token_stdin")]
+    gh_token_file: Option<PathBuf>,
+
+    /// Read the GitHub token from stdin.
+    #[arg(long)]
+    gh_token_stdin: bool,
+
     /// An optional user name. If not provided, the username associated
     /// with the provided GitHub token will be used.
     #[arg(long)]
     user: Option<String>,
+
+    /// Authenticate as a GitHub App with this ID rather than with a token. This needs
+    /// --app-private-key, --app-installation-id and --user to also be given.
+    #[arg(
+        long,
+        requires_all = ["app_private_key", "app_installation_id", "user"],
+        conflicts_with_all = ["gh_token", "gh_token_file", "gh_token_stdin"]
+    )]
+    app_id: Option<String>,
+
+    /// A file containing the GitHub App's PEM encoded private key.
+    #[arg(long, requires = "app_id")]
+    app_private_key: Option<PathBuf>,
+
+    /// The ID of the installation of the GitHub App to authenticate as. The app can
+    /// only see contributions to repositories that this installation has access to.
+    #[arg(long, requires = "app_id")]
+    app_installation_id: Option<u64>,
+
+    /// The base URL of the GitHub API, which defaults to 'https://api.github.com'.
+    /// This can also be provided via the env var GITHUB_API_URL.
+    ///
+    /// For GitHub Enterprise Server, use something like
+    /// 'https://github.example.com/api/v3'.
+    #[arg(long)]
+    api_url: Option<ApiUrl>,
+
+    /// How many times to try each request to GitHub before giving up, if it
+    /// keeps failing for transient reasons like timeouts or secondary rate limits.
+    #[arg(long, default_value_t = RetryPolicy::default().max_attempts)]
+    max_attempts: u32,
+
+    /// The most requests to send to GitHub at once. Categories of contributions, and
+    /// each year of long date ranges, are fetched concurrently up to this limit.
+    #[arg(long, default_value_t = ApiOptions::default().concurrency)]
+    concurrency: usize,
+
+    /// Record every request made to GitHub, and the response to it, into the given
+    /// directory, so that the run can be replayed later with --replay.
+    #[arg(long, conflicts_with = "replay")]
+    record: Option<PathBuf>,
+
+    /// Replay a run previously recorded with --record from the given directory, rather
+    /// than talking to GitHub. The arguments given should match those of the recorded run.
+    #[arg(long)]
+    replay: Option<PathBuf>,
+
+    /// Don't read from or write to the on-disk cache of responses from GitHub.
+    #[arg(long)]
+    no_cache: bool,
+
+    /// Ignore any cached responses and fetch everything from GitHub again,
+    /// caching the new responses.
+    #[arg(long)]
+    refresh: bool,
+
+    /// How long to cache responses from GitHub for, for example '30m', '12h' or '7d'.
+    /// Responses about windows of time which ended over 30 days ago are cached
+    /// indefinitely, since they are unlikely to change.
+    ///
+    /// So that runs a little while apart ask GitHub for the same thing, dates relative to
+    /// now (including the end of the period when no --to is given) are worked out from the
+    /// time rounded down to a multiple of this. Contributions made since then show up
+    /// once the next multiple is reached, or with --no-cache.
+    #[arg(long, default_value = "1h", value_parser = dates::parse_duration)]
+    cache_ttl: Duration,
+
+    /// Also list the commits authored on the default branch of each repository that
+    /// commits were made to. This needs a query per repository.
+    #[arg(long)]
+    commit_messages: bool,
+
+    /// Rather than a summary, write each contribution as a line of JSON as soon as it's
+    /// fetched, for piping into other tools. Each has a 'type' of 'pull_request',
+    /// 'pull_request_review', 'issue', 'issue_comments', 'discussion',
+    /// 'discussion_comment', 'discussion_answer', 'repository' or 'repository_commits', and
+    /// they are in no particular order.
+    #[arg(long)]
+    stream: bool,
 }
 
-#[tokio::main]
-async fn main() -> Result<(), anyhow::Error> {
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Manage the on-disk cache of responses from GitHub, which lives in
+    /// $XDG_CACHE_HOME/github-summarizer (or ~/.cache/github-summarizer).
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum CacheCommand {
+    /// Remove everything from the cache.
+    Clear,
+    /// Show how much is in the cache.
+    Stats,
+}
+
+fn main() -> Result<(), anyhow::Error> {
     let opts = Cli::parse();
 
-    let from = opts.from;
-    let to = DateTime::now();
+    if let Some(Command::Cache { command }) = &opts.command {
+        return run_cache_command(command);
+    }
+
+    let recording = match (&opts.record, &opts.replay) {
+        (Some(dir), _) => Some(Recording::Record(dir.clone())),
+        (_, Some(dir)) => Some(Recording::Replay(dir.clone())),
+        (None, None) => None,
+    };
+
+    // When replaying, we resolve dates as they were when recorded so that we make the
+    // same requests. Otherwise, if we're caching (which we never do when recording or
+    // replaying), we round now down to the cache TTL so that runs within it send the same
+    // queries and can be answered from the cache.
+    //
+    // The local offset can only be reliably obtained while the process has a single
+    // thread, so we resolve our dates before starting the async runtime.
+    let recorded_run = match &recording {
+        Some(recording) => recording.replay_run()?,
+        None => None,
+    };
+    let (now, offset) = match recorded_run {
+        Some(run) => (run.now, UtcOffset::from_whole_seconds(run.offset_seconds)?),
+        None => (
+            DateTime::now(),
+            UtcOffset::current_local_offset().unwrap_or(UtcOffset::UTC),
+        ),
+    };
+    let offset = opts.timezone.unwrap_or(offset);
+    let from = opts
+        .from
+        .ok_or_else(|| anyhow::anyhow!("--from is required"))?;
+    let resolve = |now| {
+        let to = opts.to.map(|to| to.end(now, offset)).unwrap_or(now);
+        (from.start(now, offset), to)
+    };
+    let (from, to) = match cache_for(&opts, &recording) {
+        // Rounding could put now before a --from in the last little while, in which case
+        // there's nothing for it but to ask about up to now.
+        Some(cache) => match resolve(now.rounded_down(cache.ttl())) {
+            (from, to) if from < to => (from, to),
+            _ => resolve(now),
+        },
+        None => resolve(now),
+    };
+
+    if let Some(recording) = &recording {
+        recording.record_run(&RecordedRun {
+            now,
+            offset_seconds: offset.whole_seconds(),
+        })?;
+    }
+
+    if from >= to {
+        return Err(anyhow::anyhow!(
+            "The --from date ({from}) must be before the --to date ({to})"
+        ));
+    }
+
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?
+        .block_on(run(opts, recording, from, to))
+}
+
+async fn run(
+    opts: Cli,
+    recording: Option<Recording>,
+    from: DateTime,
+    to: DateTime,
+) -> Result<(), anyhow::Error> {
+    let cache = cache_for(&opts, &recording);
+    let is_replaying = matches!(recording, Some(Recording::Replay(_)));
 
-    let gh_token = opts
-        .gh_token
-        .or_else(|| std::env::var("GITHUB_TOKEN").ok())
-        .ok_or_else(|| {
-            anyhow::anyhow!(
-                "GITHUB_TOKEN must be set either via --gh-token or GITHUB_TOKEN env var"
+    let api_url = match opts.api_url.clone() {
+        Some(url) => url,
+        None => match std::env::var("GITHUB_API_URL") {
+            Ok(url) => url.parse()?,
+            Err(_) => ApiUrl::default(),
+        },
+    };
+
+    let auth = if let (Some(app_id), Some(key_path), Some(installation_id)) = (
+        &opts.app_id,
+        &opts.app_private_key,
+        opts.app_installation_id,
+    ) {
+        let key = std::fs::read(key_path).with_context(|| {
+            format!(
+                "Failed to read GitHub App private key from {}",
+                key_path.display()
             )
         })?;
+        Auth::App(GitHubApp::new(app_id.clone(), &key, installation_id)?)
+    } else {
+        Auth::Token(find_token(&opts, &api_url, is_replaying)?)
+    };
 
     // Spin up an API client to talk to github.
-    let api = Api::new(gh_token, opts.user).await?;
+    let api_options = ApiOptions {
+        url: api_url,
+        retry: RetryPolicy {
+            max_attempts: opts.max_attempts.max(1),
+            ..RetryPolicy::default()
+        },
+        recording,
+        cache,
+        concurrency: opts.concurrency.max(1),
+    };
+    let api = Api::new(auth, opts.user, api_options).await?;
+
+    if opts.stream {
+        stream_contributions(&api, from, to, opts.commit_messages)
+            .await
+            .map_err(|e| explain_error(e, api.user()))?;
+        eprintln!("{}", api.rate_limit().summary());
+        return Ok(());
+    }
 
     // Get the data.
-    let prs = api::pull_requests::query(&api, from, to).await?;
-    let issues = api::issues::query(&api, from, to).await?;
-    let repositories = api::repositories::query(&api, from, to).await?;
+    let fetch_contributions = async {
+        let first_pages = FirstPages::fetch(&api, &BATCHED_KINDS, from, to).await?;
+        let kinds = tokio::try_join!(
+            contributions::query::<PullRequests>(&api, &first_pages),
+            contributions::query::<PullRequestReviews>(&api, &first_pages),
+            contributions::query::<Issues>(&api, &first_pages),
+            contributions::query::<Repositories>(&api, &first_pages),
+        )?;
+        Ok::<_, anyhow::Error>((first_pages, kinds))
+    };
+    let fetch = async {
+        tokio::try_join!(
+            fetch_contributions,
+            comments::query(&api, from, to),
+            discussions::query(&api, from, to),
+            discussions::query_comments(&api, from, to),
+            discussions::query_answers(&api, from, to),
+            commits::query(&api, from, to, opts.commit_messages),
+        )
+    };
+    let (
+        (first_pages, (prs, reviews, issues, repositories)),
+        comments,
+        discussions,
+        discussion_comments,
+        answers,
+        commits,
+    ) = fetch.await.map_err(|e| explain_error(e, api.user()))?;
+    let skipped = [
+        (prs.skipped, PullRequests::NAME),
+        (reviews.skipped, PullRequestReviews::NAME),
+        (issues.skipped, Issues::NAME),
+        (comments.skipped, comments::NAME),
+        (discussions.skipped, discussions::NAME),
+        (discussion_comments.skipped, discussions::COMMENTS_NAME),
+        (answers.skipped, discussions::ANSWERS_NAME),
+        (repositories.skipped, Repositories::NAME),
+    ];
+    let (prs, reviews, issues, comments, discussions, discussion_comments, answers, repositories) = (
+        prs.items,
+        reviews.items,
+        issues.items,
+        comments.items,
+        discussions.items,
+        discussion_comments.items,
+        answers.items,
+        repositories.items,
+    );
+    let (commits, commits_truncated) = (commits.repositories, commits.truncated);
 
     // Some summary figures.
     let prs_count = prs.len();
@@ -56,22 +367,48 @@ async fn main() -> Result<(), anyhow::Error> {
         .iter()
         .filter(|pr| pr.state == utils::ItemState::Merged)
         .count();
+    let reviews_count = reviews.len();
+    let reviewed_prs_count = reviews
+        .iter()
+        .map(|review| &review.pull_request_url)
+        .collect::<std::collections::HashSet<_>>()
+        .len();
+    let approved_reviews_count = reviews
+        .iter()
+        .filter(|review| review.state == ReviewState::Approved)
+        .count();
+    let changes_requested_reviews_count = reviews
+        .iter()
+        .filter(|review| review.state == ReviewState::ChangesRequested)
+        .count();
     let issues_count = issues.len();
     let merged_issues_count = issues
         .iter()
         .filter(|issue| issue.state == utils::ItemState::Closed)
         .count();
+    let comments_count: usize = comments.iter().map(|item| item.comments.len()).sum();
+    let commented_items_count = comments.len();
+    let discussions_count = discussions.len();
+    let answered_discussions_count = discussions
+        .iter()
+        .filter(|discussion| discussion.answer_chosen_at.is_some())
+        .count();
+    let discussion_comments_count = discussion_comments.len();
+    let answers_count = answers.len();
     let non_forked_repository_count = repositories
         .iter()
         .filter(|repo| repo.original_owner.is_none())
         .count();
 
+    let commits_count: usize = commits.iter().map(|repo| repo.commit_count).sum();
+    let committed_repositories_count = commits.len();
+
     let mut out = String::new();
     use std::fmt::Write;
 
     writeln!(
         out,
-        "Below is a summary of what I've worked on in GitHub since {from}."
+        "Below is a summary of what I've worked on in GitHub between {from} and {to}."
     )?;
     writeln!(out,)?;
     writeln!(out, "First, the issues that I've opened, in JSON:")?;
@@ -88,6 +425,79 @@ async fn main() -> Result<(), anyhow::Error> {
         writeln!(out, "{s}")?;
     }
     writeln!(out,)?;
+    writeln!(
+        out,
+        "Then, the reviews that I've left on pull requests, in JSON:"
+    )?;
+    writeln!(out,)?;
+    for val in reviews {
+        let s = serde_json::to_string_pretty(&val)?;
+        writeln!(out, "{s}")?;
+    }
+    writeln!(out,)?;
+    writeln!(
+        out,
+        "Then, the issues and pull requests that I've commented on, along with my comments, in JSON:"
+    )?;
+    writeln!(out,)?;
+    for val in comments {
+        let s = serde_json::to_string_pretty(&val)?;
+        writeln!(out, "{s}")?;
+    }
+    writeln!(out,)?;
+    writeln!(out, "Then, the discussions that I've started, in JSON:")?;
+    writeln!(out,)?;
+    for val in discussions {
+        let s = serde_json::to_string_pretty(&val)?;
+        writeln!(out, "{s}")?;
+    }
+    writeln!(out,)?;
+    writeln!(
+        out,
+        "Then, the comments that I've left on discussions, in JSON:"
+    )?;
+    writeln!(out,)?;
+    for val in discussion_comments {
+        let s = serde_json::to_string_pretty(&val)?;
+        writeln!(out, "{s}")?;
+    }
+    writeln!(out,)?;
+    writeln!(
+        out,
+        "Then, those of my comments on discussions which were accepted as the answer, in JSON:"
+    )?;
+    writeln!(out,)?;
+    for val in answers {
+        let s = serde_json::to_string_pretty(&val)?;
+        writeln!(out, "{s}")?;
+    }
+    writeln!(out,)?;
+    writeln!(
+        out,
+        "Then, the commits that I've made to each repository (including those pushed directly rather than through pull requests):"
+    )?;
+    writeln!(out,)?;
+    write_commits_table(&mut out, &commits)?;
+    if opts.commit_messages {
+        writeln!(out,)?;
+        writeln!(
+            out,
+            "And the commits that I authored on the default branch of each of those repositories, in JSON:"
+        )?;
+        writeln!(out,)?;
+        for repo in &commits {
+            for commit in &repo.commits {
+                let mut json = serde_json::to_value(commit)?;
+                if let Some(object) = json.as_object_mut() {
+                    let name = format!("{}/{}", repo.owner, repo.repository);
+                    object.insert("repository".to_owned(), name.into());
+                }
+                let s = serde_json::to_string_pretty(&json)?;
+                writeln!(out, "{s}")?;
+            }
+        }
+    }
+    writeln!(out,)?;
     writeln!(
         out,
         "Finally, the repositories that I've created or forked (forks have a non-null 'original_owner' field), in JSON:"
@@ -103,6 +513,26 @@ async fn main() -> Result<(), anyhow::Error> {
         out,
         "- Opened {prs_count} pull requests, of which {merged_prs_count} were merged."
     )?;
+    writeln!(
+        out,
+        "- Left {reviews_count} reviews on {reviewed_prs_count} pull requests, of which {approved_reviews_count} approved and {changes_requested_reviews_count} requested changes."
+    )?;
+    writeln!(
+        out,
+        "- Left {comments_count} comments on {commented_items_count} issues and pull requests."
+    )?;
+    writeln!(
+        out,
+        "- Started {discussions_count} discussions, of which {answered_discussions_count} have been answered."
+    )?;
+    writeln!(
+        out,
+        "- Left {discussion_comments_count} comments on discussions, of which {answers_count} were marked as the answer."
+    )?;
+    writeln!(
+        out,
+        "- Made {commits_count} commits to {committed_repositories_count} repositories."
+    )?;
     writeln!(
         out,
         "- Opened {issues_count} issues, of which {merged_issues_count} have been closed."
@@ -111,7 +541,236 @@ async fn main() -> Result<(), anyhow::Error> {
         out,
         "- Created {non_forked_repository_count} repositories (not counting forks)."
     )?;
+    if let Some(note) = restricted_note(first_pages.restricted()) {
+        writeln!(out, "- {note}")?;
+    }
+    if !first_pages.has_activity_in_the_past() {
+        writeln!(out, "- Had no activity on GitHub before this period.")?;
+    }
+
+    // GitHub may give back partial results, so be upfront about anything missing.
+    let warnings = api.warnings();
+    if skipped.iter().any(|(n, _)| *n > 0) || commits_truncated || !warnings.is_empty() {
+        writeln!(out,)?;
+        writeln!(
+            out,
+            "Note that some items could not be retrieved from GitHub, and are missing from the above:"
+        )?;
+        for (n, what) in skipped.into_iter().filter(|(n, _)| *n > 0) {
+            writeln!(out, "- {n} {what} were skipped.")?;
+        }
+        if commits_truncated {
+            writeln!(
+                out,
+                "- Commits were made to more repositories than GitHub will list at once, so \
+                commits to some repositories may be missing."
+            )?;
+        }
+        for warning in warnings {
+            writeln!(out, "- GitHub said: {warning}")?;
+        }
+    }
 
     println!("{out}");
+    eprintln!("{}", api.rate_limit().summary());
+    Ok(())
+}
+
+/// A note about the contributions that the token can't see, if there are any, so that
+/// nobody mistakes the summary for a complete one.
+fn restricted_note(restricted: Restricted) -> Option<String> {
+    if restricted.count > 0 {
+        Some(format!(
+            "Plus {} contributions in private repositories not visible with this token.",
+            restricted.count
+        ))
+    } else if restricted.any {
+        Some("Plus some contributions in private repositories not visible with this token.".into())
+    } else {
+        None
+    }
+}
+
+/// Write a table of how many commits were made to each repository, and when.
+fn write_commits_table(
+    out: &mut String,
+    commits: &[RepositoryCommits],
+) -> Result<(), std::fmt::Error> {
+    use std::fmt::Write;
+
+    if commits.is_empty() {
+        return writeln!(out, "(none)");
+    }
+    writeln!(
+        out,
+        "| Repository | Commits | Days with commits | First commit | Last commit |"
+    )?;
+    writeln!(out, "| --- | --- | --- | --- | --- |")?;
+    for repo in commits {
+        let first = repo.days.first().map(|day| day.date.0.date());
+        let last = repo.days.last().map(|day| day.date.0.date());
+        writeln!(
+            out,
+            "| {}/{} | {} | {} | {} | {} |",
+            repo.owner,
+            repo.repository,
+            repo.commit_count,
+            repo.days.len(),
+            first.map(|d| d.to_string()).unwrap_or_default(),
+            last.map(|d| d.to_string()).unwrap_or_default(),
+        )?;
+    }
+    Ok(())
+}
+
+/// Write each contribution to stdout as a line of JSON as soon as it's fetched.
+async fn stream_contributions(
+    api: &Api,
+    from: DateTime,
+    to: DateTime,
+    commit_messages: bool,
+) -> Result<(), anyhow::Error> {
+    fn json_line<T: serde::Serialize>(item_type: &str, item: T) -> Result<String, anyhow::Error> {
+        let mut json = serde_json::to_value(item)?;
+        if let Some(object) = json.as_object_mut() {
+            object.insert("type".to_owned(), item_type.into());
+        }
+        Ok(json.to_string())
+    }
+
+    let first_pages = stream::once(Box::pin(FirstPages::fetch(api, &BATCHED_KINDS, from, to)));
+    let contributions = first_pages
+        .map_ok(|first_pages| {
+            if let Some(note) = restricted_note(first_pages.restricted()) {
+                eprintln!("{note}");
+            }
+            let prs = contributions::stream::<PullRequests>(api, &first_pages)
+                .and_then(|item| future::ready(json_line("pull_request", item)));
+            let reviews = contributions::stream::<PullRequestReviews>(api, &first_pages)
+                .and_then(|item| future::ready(json_line("pull_request_review", item)));
+            let issues = contributions::stream::<Issues>(api, &first_pages)
+                .and_then(|item| future::ready(json_line("issue", item)));
+            let repositories = contributions::stream::<Repositories>(api, &first_pages)
+                .and_then(|item| future::ready(json_line("repository", item)));
+            stream::select(
+                stream::select(prs, reviews),
+                stream::select(issues, repositories),
+            )
+        })
+        .try_flatten();
+
+    // Commits are counted per repository, so we can only write them once they're all in.
+    let commits = stream::once(Box::pin(commits::query(api, from, to, commit_messages)))
+        .map_ok(|commits| stream::iter(commits.repositories.into_iter().map(Ok)))
+        .try_flatten()
+        .and_then(|repo| future::ready(json_line("repository_commits", repo)));
+
+    // Comments are grouped by what they were left on, so likewise for them.
+    let comments = stream::once(Box::pin(comments::query(api, from, to)))
+        .map_ok(|comments| stream::iter(comments.items.into_iter().map(Ok)))
+        .try_flatten()
+        .and_then(|item| future::ready(json_line("issue_comments", item)));
+
+    let discussions = discussions::stream(api, from, to)
+        .and_then(|item| future::ready(json_line("discussion", item)));
+    let discussion_comments = discussions::stream_comments(api, false, from, to)
+        .and_then(|item| future::ready(json_line("discussion_comment", item)));
+    let answers = discussions::stream_comments(api, true, from, to)
+        .and_then(|item| future::ready(json_line("discussion_answer", item)));
+
+    let mut lines = std::pin::pin!(stream::select(
+        stream::select(contributions, stream::select(comments, commits)),
+        stream::select(discussions, stream::select(discussion_comments, answers)),
+    ));
+    while let Some(line) = lines.try_next().await? {
+        println!("{line}");
+    }
+
+    // Anything that GitHub couldn't give us has already been warned about on stderr.
+    Ok(())
+}
+
+/// Find the token to use from the arguments given, or anywhere else that we can.
+fn find_token(opts: &Cli, api_url: &ApiUrl, is_replaying: bool) -> Result<String, anyhow::Error> {
+    let given = if let Some(token) = &opts.gh_token {
+        Some(GivenToken::Arg(token.clone()))
+    } else if let Some(path) = &opts.gh_token_file {
+        Some(GivenToken::File(path.clone()))
+    } else if opts.gh_token_stdin {
+        Some(GivenToken::Stdin)
+    } else {
+        None
+    };
+
+    // No token is needed to replay a recording, so don't go looking for one.
+    if given.is_none() && is_replaying {
+        return Ok(String::new());
+    }
+
+    let (token, source) = credentials::find_token(given, &api_url.host())?.ok_or_else(|| {
+        anyhow::anyhow!(
+            "No GitHub token found. Provide one via --gh-token-file, --gh-token-stdin, \
+            --gh-token or the GITHUB_TOKEN env var, or log in with 'gh auth login'"
+        )
+    })?;
+    if matches!(source, TokenSource::GhCli | TokenSource::GitCredential) {
+        eprintln!("Using the GitHub token from {source}");
+    }
+    Ok(token)
+}
+
+/// Replace errors that we know the likely cause of with more helpful ones.
+fn explain_error(err: anyhow::Error, user: &str) -> anyhow::Error {
+    let Some(api_error) = err.downcast_ref::<ApiError>() else {
+        return err;
+    };
+    match api_error {
+        ApiError::NotFound(e) if e.is_at("user") => {
+            anyhow::anyhow!("GitHub user '{user}' not found. Is the --user argument correct?")
+        }
+        ApiError::Forbidden(e) => anyhow::anyhow!(
+            "GitHub refused to give us some data: {e}\n\
+            Check that your token has access to the user's contributions."
+        ),
+        ApiError::RateLimited(e) => anyhow::anyhow!(
+            "GitHub's rate limit was exceeded: {e}\n\
+            Try again once it has reset, or use --max-attempts to wait for longer."
+        ),
+        ApiError::MaxNodeLimitExceeded(e) => anyhow::anyhow!(
+            "GitHub considers one of our queries to be too large: {e}\n\
+            This is a bug; please report it."
+        ),
+        _ => err,
+    }
+}
+
+/// The cache to use, if any. We don't use it when recording or replaying, so that
+/// exactly what was sent to GitHub is recorded, and replayed.
+fn cache_for(opts: &Cli, recording: &Option<Recording>) -> Option<Cache> {
+    if opts.no_cache || recording.is_some() {
+        return None;
+    }
+    let dir = Cache::default_dir()?;
+    Some(Cache::new(dir, opts.cache_ttl, opts.refresh))
+}
+
+fn run_cache_command(command: &CacheCommand) -> Result<(), anyhow::Error> {
+    let dir = Cache::default_dir()
+        .ok_or_else(|| anyhow::anyhow!("Could not find a cache directory; is $HOME set?"))?;
+
+    match command {
+        CacheCommand::Clear => {
+            let removed = Cache::clear(&dir)?;
+            println!("Removed {removed} cached responses from {}", dir.display());
+        }
+        CacheCommand::Stats => {
+            let stats = Cache::stats(&dir)?;
+            println!("Cache directory: {}", dir.display());
+            println!("Cached responses: {}", stats.entries);
+            println!("  of which historical: {}", stats.historical);
+            println!("  of which expired: {}", stats.expired);
+            println!("Size: {:.1} KiB", stats.bytes as f64 / 1024.0);
+        }
+    }
     Ok(())
 }
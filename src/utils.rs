@@ -1,3 +1,4 @@
+use std::collections::HashSet;
 use std::fmt;
 use std::str::FromStr;
 use time::format_description::well_known::Iso8601;
@@ -10,6 +11,20 @@ impl DateTime {
     pub fn now() -> Self {
         DateTime(time::OffsetDateTime::now_utc())
     }
+
+    /// This time rounded down to a multiple of `step` since the Unix epoch, so that times
+    /// less than `step` apart usually agree on it.
+    pub fn rounded_down(self, step: time::Duration) -> Self {
+        let step = step.whole_seconds();
+        if step <= 0 {
+            return self;
+        }
+        DateTime(
+            self.0
+                - time::Duration::seconds(self.0.unix_timestamp().rem_euclid(step))
+                - time::Duration::nanoseconds(self.0.nanosecond().into()),
+        )
+    }
 }
 
 impl serde::ser::Serialize for DateTime {
@@ -53,6 +68,37 @@ impl fmt::Display for DateTime {
     }
 }
 
+/// GitHub will only hand back contributions for a span of at most one year at a time.
+/// This splits the range `from..to` into consecutive windows which each respect that.
+pub fn year_windows(from: DateTime, to: DateTime) -> Vec<(DateTime, DateTime)> {
+    // A year is never shorter than this, so windows this long are always accepted.
+    const MAX_WINDOW: time::Duration = time::Duration::days(365);
+
+    let mut windows = vec![];
+    let mut start = from;
+    while start < to {
+        let end = DateTime(start.0.saturating_add(MAX_WINDOW)).min(to);
+        windows.push((start, end));
+        start = end;
+    }
+    windows
+}
+
+/// Remove any items which share a key with an earlier item, keeping the first seen.
+pub fn dedup_by_key<T>(items: &mut Vec<T>, key: impl Fn(&T) -> &str) {
+    let mut seen = HashSet::new();
+    items.retain(|item| seen.insert(key(item).to_owned()));
+}
+
+/// A hex encoded SHA-256 hash of some bytes, handy for naming files after their contents.
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    use sha2::Digest;
+    sha2::Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
 /// Items like PRs and issues have a state in the girhub API. This enum can represent that.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ItemState {
@@ -98,3 +144,59 @@ impl serde::ser::Serialize for ItemState {
         serializer.serialize_str(s)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn dt(s: &str) -> DateTime {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn year_windows_cover_range() {
+        let windows = year_windows(dt("2020-01-01T00:00Z"), dt("2022-06-01T00:00Z"));
+        assert_eq!(
+            windows,
+            vec![
+                (dt("2020-01-01T00:00Z"), dt("2020-12-31T00:00Z")),
+                (dt("2020-12-31T00:00Z"), dt("2021-12-31T00:00Z")),
+                (dt("2021-12-31T00:00Z"), dt("2022-06-01T00:00Z")),
+            ]
+        );
+
+        let short = year_windows(dt("2020-01-01T00:00Z"), dt("2020-01-02T00:00Z"));
+        assert_eq!(short.len(), 1);
+
+        assert!(year_windows(dt("2020-01-02T00:00Z"), dt("2020-01-01T00:00Z")).is_empty());
+    }
+
+    #[test]
+    fn rounds_down_to_steps() {
+        let hour = time::Duration::hours(1);
+        assert_eq!(
+            dt("2025-03-01T12:34:56.789Z").rounded_down(hour),
+            dt("2025-03-01T12:00:00Z")
+        );
+        assert_eq!(
+            dt("2025-03-01T12:00:00Z").rounded_down(hour),
+            dt("2025-03-01T12:00:00Z")
+        );
+        // Steps are counted in UTC, whatever the offset.
+        assert_eq!(
+            dt("2025-03-01T12:34:00+05:30").rounded_down(hour),
+            dt("2025-03-01T12:30:00+05:30")
+        );
+        assert_eq!(
+            dt("2025-03-01T12:34:56.789Z").rounded_down(time::Duration::ZERO),
+            dt("2025-03-01T12:34:56.789Z")
+        );
+    }
+
+    #[test]
+    fn dedup_keeps_first_of_each_key() {
+        let mut items = vec![("a", 1), ("b", 2), ("a", 3), ("c", 4), ("b", 5)];
+        dedup_by_key(&mut items, |item| item.0);
+        assert_eq!(items, vec![("a", 1), ("b", 2), ("c", 4)]);
+    }
+}
@@ -0,0 +1,793 @@
+//! An in-process stand-in for the bits of the GitHub API that we use, and
+//! helpers to run the binary against it.
+
+#![allow(dead_code)]
+
+use serde_json::{Map, Value, json};
+use std::collections::{BTreeMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::process::Output;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use time::OffsetDateTime;
+use time::format_description::well_known::Iso8601;
+use tokio::io::AsyncWriteExt;
+use wiremock::matchers::{method, path};
+use wiremock::{Match, Mock, MockServer, Request, Respond, ResponseTemplate};
+
+/// A fake GitHub, serving contributions from the given data.
+pub struct FakeGitHub {
+    pub server: MockServer,
+    /// Runs against this server cache responses here rather than in the user's cache.
+    pub cache_dir: TempDir,
+    /// Runs against this server look for GitHub CLI config here rather than in the user's.
+    pub gh_config_dir: TempDir,
+    /// How long to wait before responding to each GraphQL request.
+    graphql_delay: Arc<Mutex<Duration>>,
+    /// The query whose next response uses up the rate limit, and when it resets.
+    exhausted_by: Arc<Mutex<Option<(String, i64)>>>,
+    /// The GraphQL requests that are being answered.
+    in_flight: Arc<Mutex<InFlight>>,
+    /// Extra headers to send with each response to a GraphQL request.
+    graphql_headers: Arc<Mutex<Vec<(String, String)>>>,
+}
+
+/// Keeps track of how many GraphQL requests are being answered at once. Responses are sent
+/// once the delay has passed, so a request is in flight until then.
+#[derive(Default)]
+struct InFlight {
+    /// When the requests which may still be in flight arrived, oldest first.
+    arrived: VecDeque<Instant>,
+    /// The most requests that have been in flight at once.
+    peak: usize,
+}
+
+/// The contributions that the fake GitHub knows about.
+#[derive(Debug, Clone, Default)]
+pub struct Contributions {
+    pub pull_requests: Vec<Value>,
+    pub pull_request_reviews: Vec<Value>,
+    pub issues: Vec<Value>,
+    pub issue_comments: Vec<Value>,
+    pub discussions: Vec<Value>,
+    pub discussion_comments: Vec<Value>,
+    pub repositories: Vec<Value>,
+    pub commits: Vec<Value>,
+    /// When contributions that the token can't see were made.
+    pub restricted_contributions: Vec<Value>,
+    /// The user did nothing before the windows of time asked about.
+    pub new_user: bool,
+}
+
+impl FakeGitHub {
+    /// Start a fake GitHub which serves the given contributions for the given user.
+    pub async fn start(login: &str, contributions: Contributions) -> FakeGitHub {
+        let server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/user"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("X-OAuth-Scopes", "repo, read:org")
+                    .set_body_json(json!({ "login": login })),
+            )
+            .mount(&server)
+            .await;
+
+        let graphql_delay = Arc::new(Mutex::new(Duration::ZERO));
+        let exhausted_by = Arc::new(Mutex::new(None));
+        let in_flight = Arc::new(Mutex::new(InFlight::default()));
+        let graphql_headers = Arc::new(Mutex::new(vec![]));
+        Mock::given(method("POST"))
+            .and(path("/graphql"))
+            .respond_with(GraphQlResponder {
+                contributions,
+                delay: graphql_delay.clone(),
+                exhausted_by: exhausted_by.clone(),
+                in_flight: in_flight.clone(),
+                headers: graphql_headers.clone(),
+            })
+            .mount(&server)
+            .await;
+
+        FakeGitHub {
+            server,
+            cache_dir: TempDir::new("cache"),
+            gh_config_dir: TempDir::new("gh-config"),
+            graphql_delay,
+            exhausted_by,
+            in_flight,
+            graphql_headers,
+        }
+    }
+
+    /// The URL to pass to `--api-url`.
+    pub fn url(&self) -> String {
+        self.server.uri()
+    }
+
+    /// Run the binary with the given arguments against this server.
+    pub async fn run(&self, args: &[&str]) -> Output {
+        self.run_without_token(&[&["--gh-token", "test-token"], args].concat(), None)
+            .await
+    }
+
+    /// Run the binary against this server without giving it a token, writing `stdin`
+    /// to it if given.
+    pub async fn run_without_token(&self, args: &[&str], stdin: Option<&str>) -> Output {
+        let mut child = self
+            .command()
+            .args(["--api-url", &self.url(), "--timezone", "UTC"])
+            .args(args)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .expect("failed to run github-summarizer");
+
+        let mut child_stdin = child.stdin.take().expect("stdin should be piped");
+        if let Some(stdin) = stdin {
+            child_stdin
+                .write_all(stdin.as_bytes())
+                .await
+                .expect("failed to write to stdin");
+        }
+        drop(child_stdin);
+
+        child
+            .wait_with_output()
+            .await
+            .expect("failed to run github-summarizer")
+    }
+
+    /// Run a subcommand like `cache stats`, using the same cache as runs against this server.
+    pub async fn run_subcommand(&self, args: &[&str]) -> String {
+        let output = self
+            .command()
+            .args(args)
+            .output()
+            .await
+            .expect("failed to run github-summarizer");
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).expect("output should be UTF-8")
+    }
+
+    fn command(&self) -> tokio::process::Command {
+        let mut cmd = tokio::process::Command::new(env!("CARGO_BIN_EXE_github-summarizer"));
+        cmd.env_remove("GITHUB_TOKEN")
+            .env_remove("GITHUB_API_URL")
+            .env("XDG_CACHE_HOME", self.cache_dir.path())
+            .env("GH_CONFIG_DIR", self.gh_config_dir.path())
+            // Don't let git find any real credentials.
+            .env("GIT_CONFIG_GLOBAL", "/dev/null")
+            .env("GIT_CONFIG_NOSYSTEM", "1");
+        cmd
+    }
+
+    /// Run the binary against this server, expecting it to succeed, and return
+    /// what it printed to stdout.
+    pub async fn run_ok(&self, args: &[&str]) -> String {
+        let output = self.run(args).await;
+        assert!(
+            output.status.success(),
+            "github-summarizer failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8(output.stdout).expect("output should be UTF-8")
+    }
+
+    /// Wait for the given time before responding to each GraphQL request.
+    pub fn delay_graphql(&self, delay: Duration) {
+        *self.graphql_delay.lock().unwrap() = delay;
+    }
+
+    /// Send the given header with each response to a GraphQL request.
+    pub fn add_graphql_header(&self, name: &str, value: &str) {
+        let mut headers = self.graphql_headers.lock().unwrap();
+        headers.push((name.to_owned(), value.to_owned()));
+    }
+
+    /// The most GraphQL requests that have been in flight at once since this was last asked.
+    pub fn take_peak_graphql_in_flight(&self) -> usize {
+        std::mem::take(&mut self.in_flight.lock().unwrap().peak)
+    }
+
+    /// Say that the rate limit has been used up, until `reset_in` from now, in the next
+    /// response to the query with the given name.
+    pub fn exhaust_rate_limit(&self, query_name: &str, reset_in: Duration) {
+        let reset = rate_limit_reset(reset_in);
+        *self.exhausted_by.lock().unwrap() = Some((query_name.to_owned(), reset));
+    }
+
+    /// Respond to the next `n` GraphQL requests for the query with the given name
+    /// with the given response, rather than serving contributions.
+    pub async fn fail_next_queries(&self, query_name: &str, n: u64, response: ResponseTemplate) {
+        Mock::given(method("POST"))
+            .and(path("/graphql"))
+            .and(OperationName(query_name.to_owned()))
+            .respond_with(response)
+            .up_to_n_times(n)
+            .with_priority(1)
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Respond to requests for the token's user with the given response.
+    pub async fn respond_to_user(&self, response: ResponseTemplate) {
+        Mock::given(method("GET"))
+            .and(path("/user"))
+            .respond_with(response)
+            .with_priority(1)
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Act as GitHub's token endpoint for the given installation of a GitHub App, handing
+    /// out tokens which expire after `expires_in`. JWTs must be signed by the key in
+    /// `tests/fixtures/app-private-key.pem` and be for the given app.
+    pub async fn serve_installation_tokens(
+        &self,
+        app_id: &str,
+        installation_id: u64,
+        expires_in: time::Duration,
+    ) {
+        Mock::given(method("POST"))
+            .and(path(format!(
+                "/app/installations/{installation_id}/access_tokens"
+            )))
+            .respond_with(InstallationTokenResponder {
+                app_id: app_id.to_owned(),
+                expires_in,
+                issued: AtomicUsize::new(0),
+            })
+            .mount(&self.server)
+            .await;
+    }
+
+    /// The number of installation tokens handed out so far.
+    pub async fn installation_tokens_issued(&self) -> usize {
+        self.server
+            .received_requests()
+            .await
+            .unwrap_or_default()
+            .iter()
+            .filter(|req| req.url.path().ends_with("/access_tokens"))
+            .count()
+    }
+
+    /// The tokens sent with each request made so far.
+    pub async fn tokens_used(&self) -> Vec<String> {
+        self.server
+            .received_requests()
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|req| {
+                let auth = req.headers.get("authorization")?.to_str().ok()?;
+                Some(auth.strip_prefix("Bearer ")?.to_owned())
+            })
+            .collect()
+    }
+
+    /// The bodies of the GraphQL requests that have been made so far.
+    pub async fn graphql_requests(&self) -> Vec<Value> {
+        self.server
+            .received_requests()
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|req| req.url.path() == "/graphql")
+            .map(|req| req.body_json().expect("GraphQL requests should be JSON"))
+            .collect()
+    }
+
+    /// The GraphQL requests made for the query with the given name.
+    pub async fn requests_for(&self, query_name: &str) -> Vec<Value> {
+        self.graphql_requests()
+            .await
+            .into_iter()
+            .filter(|body| operation_name(body) == query_name)
+            .collect()
+    }
+}
+
+struct GraphQlResponder {
+    contributions: Contributions,
+    delay: Arc<Mutex<Duration>>,
+    exhausted_by: Arc<Mutex<Option<(String, i64)>>>,
+    in_flight: Arc<Mutex<InFlight>>,
+    headers: Arc<Mutex<Vec<(String, String)>>>,
+}
+
+impl Respond for GraphQlResponder {
+    fn respond(&self, req: &Request) -> ResponseTemplate {
+        let body: Value = req.body_json().expect("GraphQL requests should be JSON");
+        let vars = &body["variables"];
+        let name = operation_name(&body);
+        let data = match name.as_str() {
+            "CommitContributions" => self.commit_contributions(vars),
+            "CommitHistory" => self.commit_history(vars),
+            "IssueComments" => self.issue_comments(vars),
+            "RepositoryDiscussions" => self.discussions(vars),
+            "DiscussionComments" => self.discussion_comments(vars),
+            _ => self.contributions(&name, vars),
+        };
+
+        let delay = *self.delay.lock().unwrap();
+        let mut in_flight = self.in_flight.lock().unwrap();
+        let now = Instant::now();
+        while in_flight
+            .arrived
+            .front()
+            .is_some_and(|arrived| now.duration_since(*arrived) >= delay)
+        {
+            in_flight.arrived.pop_front();
+        }
+        in_flight.arrived.push_back(now);
+        in_flight.peak = in_flight.peak.max(in_flight.arrived.len());
+        drop(in_flight);
+
+        let mut exhausted_by = self.exhausted_by.lock().unwrap();
+        let (remaining, reset) = match exhausted_by.take() {
+            Some((query_name, reset)) if query_name == name => (0, reset),
+            other => {
+                *exhausted_by = other;
+                (4999, rate_limit_reset(Duration::from_secs(3600)))
+            }
+        };
+        let mut response = ResponseTemplate::new(200)
+            .set_delay(delay)
+            .insert_header("X-RateLimit-Remaining", remaining.to_string())
+            .insert_header("X-RateLimit-Reset", reset.to_string())
+            .set_body_json(json!({ "data": data }));
+        for (name, value) in self.headers.lock().unwrap().iter() {
+            response = response.insert_header(name.as_str(), value.as_str());
+        }
+        response
+    }
+}
+
+impl GraphQlResponder {
+    fn contributions(&self, name: &str, vars: &Value) -> Value {
+        // The first page of every kind of contribution is asked for together, and then
+        // each kind is paged through on its own.
+        let kinds = [
+            (
+                "PullRequestContributions",
+                "pull_request_contributions",
+                "pull_request",
+                &self.contributions.pull_requests,
+            ),
+            (
+                "PullRequestReviewContributions",
+                "pull_request_review_contributions",
+                "pull_request_review",
+                &self.contributions.pull_request_reviews,
+            ),
+            (
+                "IssueContributions",
+                "issue_contributions",
+                "issue",
+                &self.contributions.issues,
+            ),
+            (
+                "RepositoriesCreated",
+                "repository_contributions",
+                "repository",
+                &self.contributions.repositories,
+            ),
+        ];
+        let mut connections: Map<String, Value> = kinds
+            .into_iter()
+            .filter(|(query_name, ..)| name == "Contributions" || name == *query_name)
+            .map(|(_, field, node_field, items)| {
+                let nodes = in_window(vars, "created_at", items)
+                    .into_iter()
+                    .map(|item| json!({ node_field: item }))
+                    .collect();
+                (field.to_owned(), connection(vars, nodes))
+            })
+            .collect();
+        assert!(!connections.is_empty(), "Unexpected query {name:?}");
+
+        if name == "Contributions" {
+            let restricted = self.restricted_contributions_in(vars);
+            connections.insert("restricted_contributions_count".into(), restricted.into());
+            connections.insert(
+                "has_any_restricted_contributions".into(),
+                (restricted > 0).into(),
+            );
+            connections.insert(
+                "has_activity_in_the_past".into(),
+                (!self.contributions.new_user).into(),
+            );
+        }
+
+        json!({
+            "rate_limit": { "cost": 1 },
+            "user": {
+                "contributions_collection": connections
+            }
+        })
+    }
+
+    /// How many contributions that the token can't see were made in the window asked for.
+    fn restricted_contributions_in(&self, vars: &Value) -> usize {
+        let (from, to) = (parse_date(&vars["from"]), parse_date(&vars["to"]));
+        self.contributions
+            .restricted_contributions
+            .iter()
+            .map(parse_date)
+            .filter(|date| *date >= from && *date <= to)
+            .count()
+    }
+
+    /// How many commits were made to each repository on each day.
+    fn commit_contributions(&self, vars: &Value) -> Value {
+        let mut by_repository: BTreeMap<&str, BTreeMap<&str, usize>> = BTreeMap::new();
+        for commit in in_window(vars, "committed_date", &self.contributions.commits) {
+            let repository = commit["repository"].as_str().unwrap();
+            let day = &commit["committed_date"].as_str().unwrap()[..10];
+            *by_repository
+                .entry(repository)
+                .or_default()
+                .entry(day)
+                .or_default() += 1;
+        }
+
+        // GitHub gives back the repositories with the most commits, up to the number asked
+        // for, which can be at most 100.
+        let max_repositories = vars["maxRepositories"].as_u64().unwrap() as usize;
+        assert!(max_repositories <= 100, "maxRepositories is too high");
+        let mut by_repository: Vec<(&str, BTreeMap<&str, usize>)> =
+            by_repository.into_iter().collect();
+        by_repository.sort_by_key(|(_, days)| std::cmp::Reverse(days.values().sum::<usize>()));
+        by_repository.truncate(max_repositories);
+
+        let by_repository: Vec<Value> = by_repository
+            .into_iter()
+            .map(|(repository, days)| {
+                let nodes: Vec<Value> = days
+                    .into_iter()
+                    .map(|(day, count)| {
+                        json!({ "commit_count": count, "occurred_at": format!("{day}T00:00:00Z") })
+                    })
+                    .collect();
+                json!({
+                    "repository": {
+                        "name": repository,
+                        "owner": { "login": "octocat" },
+                        "url": format!("https://github.com/octocat/{repository}"),
+                    },
+                    "contributions": { "nodes": nodes },
+                })
+            })
+            .collect();
+
+        json!({
+            "rate_limit": { "cost": 1 },
+            "user": {
+                "id": "U_octocat",
+                "contributions_collection": {
+                    "commit_contributions_by_repository": by_repository
+                }
+            }
+        })
+    }
+
+    /// A page of the commits made to a repository's default branch.
+    fn commit_history(&self, vars: &Value) -> Value {
+        assert_eq!(vars["author"], json!({ "id": "U_octocat" }));
+        let nodes = in_window(vars, "committed_date", &self.contributions.commits)
+            .into_iter()
+            .filter(|commit| commit["repository"] == vars["name"])
+            .cloned()
+            .collect();
+
+        json!({
+            "rate_limit": { "cost": 1 },
+            "repository": {
+                "default_branch_ref": {
+                    "target": { "history": connection(vars, nodes) }
+                }
+            }
+        })
+    }
+
+    /// A page of the comments that the user has left, most recently updated first.
+    fn issue_comments(&self, vars: &Value) -> Value {
+        let mut comments = self.contributions.issue_comments.clone();
+        comments.sort_by_key(|comment| std::cmp::Reverse(parse_date(&comment["updated_at"])));
+
+        json!({
+            "rate_limit": { "cost": 1 },
+            "user": { "issue_comments": connection(vars, comments) }
+        })
+    }
+
+    /// A page of the discussions that the user has started, newest first.
+    fn discussions(&self, vars: &Value) -> Value {
+        let mut discussions = self.contributions.discussions.clone();
+        discussions.sort_by_key(|d| std::cmp::Reverse(parse_date(&d["created_at"])));
+
+        json!({
+            "rate_limit": { "cost": 1 },
+            "user": { "repository_discussions": connection(vars, discussions) }
+        })
+    }
+
+    /// A page of the comments that the user has left on discussions, in no particular order,
+    /// or of just those which were accepted as the answer.
+    fn discussion_comments(&self, vars: &Value) -> Value {
+        let only_answers = vars["onlyAnswers"] == json!(true);
+        let comments = self
+            .contributions
+            .discussion_comments
+            .iter()
+            .filter(|comment| !only_answers || comment["is_answer"] == json!(true))
+            .cloned()
+            .collect();
+        json!({
+            "rate_limit": { "cost": 1 },
+            "user": { "repository_discussion_comments": connection(vars, comments) }
+        })
+    }
+}
+
+/// Like GitHub, only return items created within the window asked for.
+fn in_window<'a>(vars: &Value, date_field: &str, items: &'a [Value]) -> Vec<&'a Value> {
+    let from = parse_date(&vars["from"]);
+    let to = parse_date(&vars["to"]);
+    items
+        .iter()
+        .filter(|item| {
+            let date = parse_date(&item[date_field]);
+            date >= from && date <= to
+        })
+        .collect()
+}
+
+/// A page of a connection, like GitHub would return it for the given variables.
+fn connection(vars: &Value, items: Vec<Value>) -> Value {
+    // Cursors are just the index to start the next page from.
+    let start: usize = vars["cursor"]
+        .as_str()
+        .map(|c| c.parse().expect("cursor should be a number"))
+        .unwrap_or(0);
+    let first = vars["first"].as_u64().expect("first should be given") as usize;
+    let end = (start + first).min(items.len());
+
+    json!({
+        "page_info": {
+            "end_cursor": end.to_string(),
+            "has_next_page": end < items.len(),
+        },
+        "nodes": &items[start..end],
+    })
+}
+
+struct InstallationTokenResponder {
+    app_id: String,
+    expires_in: time::Duration,
+    issued: AtomicUsize,
+}
+
+impl Respond for InstallationTokenResponder {
+    fn respond(&self, req: &Request) -> ResponseTemplate {
+        #[derive(serde::Deserialize)]
+        struct Claims {
+            iss: String,
+        }
+
+        let Some(jwt) = req
+            .headers
+            .get("authorization")
+            .and_then(|auth| auth.to_str().ok())
+            .and_then(|auth| auth.strip_prefix("Bearer "))
+        else {
+            return ResponseTemplate::new(401).set_body_json(json!({ "message": "No JWT" }));
+        };
+
+        let public_key = include_bytes!("../fixtures/app-public-key.pem");
+        let key = jsonwebtoken::DecodingKey::from_rsa_pem(public_key).unwrap();
+        let validation = jsonwebtoken::Validation::new(jsonwebtoken::Algorithm::RS256);
+        match jsonwebtoken::decode::<Claims>(jwt, &key, &validation) {
+            Ok(jwt) if jwt.claims.iss == self.app_id => {}
+            _ => {
+                return ResponseTemplate::new(401)
+                    .set_body_json(json!({ "message": "A JSON web token could not be decoded" }));
+            }
+        }
+
+        let n = self.issued.fetch_add(1, Ordering::Relaxed);
+        let expires_at = OffsetDateTime::now_utc() + self.expires_in;
+        ResponseTemplate::new(201).set_body_json(json!({
+            "token": format!("installation-token-{n}"),
+            "expires_at": expires_at.format(&Iso8601::DEFAULT).unwrap(),
+        }))
+    }
+}
+
+/// Matches GraphQL requests for the query with the given name.
+struct OperationName(String);
+
+impl Match for OperationName {
+    fn matches(&self, req: &Request) -> bool {
+        req.body_json()
+            .is_ok_and(|body: Value| operation_name(&body) == self.0)
+    }
+}
+
+/// The name given to the query in a GraphQL request body.
+pub fn operation_name(body: &Value) -> String {
+    let query = body["query"].as_str().expect("query should be a string");
+    let query = query.trim_start().trim_start_matches("query").trim_start();
+    query
+        .split(|c: char| !c.is_alphanumeric())
+        .next()
+        .unwrap_or_default()
+        .to_owned()
+}
+
+/// The value of an `X-RateLimit-Reset` header for a reset `reset_in` from now. These are
+/// in whole seconds, so round up to be sure that it's no sooner.
+pub fn rate_limit_reset(reset_in: Duration) -> i64 {
+    let reset = OffsetDateTime::now_utc() + reset_in;
+    reset.unix_timestamp() + 1
+}
+
+/// Parse a date given in a request or response.
+pub fn parse_date(value: &Value) -> OffsetDateTime {
+    let s = value.as_str().expect("dates should be strings");
+    OffsetDateTime::parse(s, &Iso8601::DEFAULT).expect("dates should be ISO 8601")
+}
+
+/// A pull request, as selected by our query.
+pub fn pull_request(n: usize, state: &str, created_at: &str) -> Value {
+    json!({
+        "repository": { "name": "repo", "owner": { "login": "octocat" } },
+        "title": format!("Pull request {n}"),
+        "state": state,
+        "created_at": created_at,
+        "body_text": "Some changes",
+        "url": format!("https://github.com/octocat/repo/pull/{n}"),
+    })
+}
+
+/// A review of a pull request, as selected by our query. Reviews of the same pull request
+/// are told apart by when they were submitted.
+pub fn pull_request_review(n: usize, state: &str, submitted_at: &str) -> Value {
+    let id: String = submitted_at.chars().filter(char::is_ascii_digit).collect();
+    json!({
+        "pull_request": {
+            "repository": { "name": "repo", "owner": { "login": "someone-else" } },
+            "title": format!("Pull request {n}"),
+            "url": format!("https://github.com/someone-else/repo/pull/{n}"),
+        },
+        "state": state,
+        "created_at": submitted_at,
+        "submitted_at": submitted_at,
+        "body_text": "Looks good",
+        "comments": { "total_count": 2 },
+        "url": format!("https://github.com/someone-else/repo/pull/{n}#pullrequestreview-{id}"),
+    })
+}
+
+/// A comment on an issue, or on a pull request if `on_pull_request`, as selected by our
+/// query. Comments are told apart by when they were created.
+pub fn issue_comment(n: usize, on_pull_request: bool, created_at: &str, updated_at: &str) -> Value {
+    let id: String = created_at.chars().filter(char::is_ascii_digit).collect();
+    let (path, title) = if on_pull_request {
+        ("pull", format!("Pull request {n}"))
+    } else {
+        ("issues", format!("Issue {n}"))
+    };
+    let url = format!("https://github.com/someone-else/repo/{path}/{n}");
+    json!({
+        "body_text": "Have you tried turning it off and on again?",
+        "created_at": created_at,
+        "updated_at": updated_at,
+        "url": format!("{url}#issuecomment-{id}"),
+        "issue": {
+            "repository": { "name": "repo", "owner": { "login": "someone-else" } },
+            "number": n,
+            "title": title,
+            "url": url,
+        },
+        "pull_request": on_pull_request.then(|| json!({ "url": url })),
+    })
+}
+
+/// A discussion, as selected by our query. Answered if `answered_at` is given.
+pub fn discussion(n: usize, created_at: &str, answered_at: Option<&str>) -> Value {
+    json!({
+        "repository": { "name": "repo", "owner": { "login": "someone-else" } },
+        "category": { "name": "Q&A" },
+        "title": format!("Discussion {n}"),
+        "created_at": created_at,
+        "body_text": "How do I do this?",
+        "answer_chosen_at": answered_at,
+        "url": format!("https://github.com/someone-else/repo/discussions/{n}"),
+    })
+}
+
+/// A comment on a discussion, as selected by our query, along with whether it was accepted
+/// as the answer. Comments are told apart by when they were created.
+pub fn discussion_comment(n: usize, is_answer: bool, created_at: &str) -> Value {
+    let id: String = created_at.chars().filter(char::is_ascii_digit).collect();
+    let url = format!("https://github.com/someone-else/repo/discussions/{n}");
+    json!({
+        "discussion": {
+            "repository": { "name": "repo", "owner": { "login": "someone-else" } },
+            "category": { "name": "Q&A" },
+            "title": format!("Discussion {n}"),
+            "url": url,
+        },
+        "body_text": "Like this.",
+        "created_at": created_at,
+        "is_answer": is_answer,
+        "url": format!("{url}#discussioncomment-{id}"),
+    })
+}
+
+/// A commit to one of the user's repositories, as selected by our query, along with the
+/// name of the repository that it's in.
+pub fn commit(repository: &str, n: usize, committed_date: &str) -> Value {
+    json!({
+        "repository": repository,
+        "message_headline": format!("Commit {n}"),
+        "committed_date": committed_date,
+        "url": format!("https://github.com/octocat/{repository}/commit/{n}"),
+    })
+}
+
+/// An issue, as selected by our query.
+pub fn issue(n: usize, state: &str, created_at: &str) -> Value {
+    json!({
+        "repository": { "name": "repo", "owner": { "login": "octocat" } },
+        "title": format!("Issue {n}"),
+        "state": state,
+        "created_at": created_at,
+        "body_text": "Something is broken",
+        "url": format!("https://github.com/octocat/repo/issues/{n}"),
+    })
+}
+
+/// A repository, as selected by our query. A fork if `parent_owner` is given.
+pub fn repository(name: &str, parent_owner: Option<&str>, created_at: &str) -> Value {
+    json!({
+        "name": name,
+        "description": null,
+        "parent": parent_owner.map(|login| json!({ "owner": { "login": login } })),
+        "owner": { "login": "octocat" },
+        "created_at": created_at,
+        "url": format!("https://github.com/octocat/{name}"),
+    })
+}
+
+/// A temporary directory which is removed when dropped.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> TempDir {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "github-summarizer-{name}-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        TempDir(dir)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
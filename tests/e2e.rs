@@ -0,0 +1,1016 @@
+mod common;
+
+use common::{
+    Contributions, FakeGitHub, TempDir, commit, discussion, discussion_comment, issue,
+    issue_comment, pull_request, pull_request_review, repository,
+};
+use serde_json::json;
+use std::time::{Duration, Instant};
+use wiremock::ResponseTemplate;
+
+#[tokio::test]
+async fn summarizes_paginated_contributions() {
+    let contributions = Contributions {
+        pull_requests: (0..150)
+            .map(|n| {
+                let state = if n % 3 == 0 { "OPEN" } else { "MERGED" };
+                pull_request(n, state, "2025-03-01T12:00:00Z")
+            })
+            .collect(),
+        pull_request_reviews: vec![
+            pull_request_review(1, "APPROVED", "2025-02-10T00:00:00Z"),
+            pull_request_review(1, "COMMENTED", "2025-02-11T00:00:00Z"),
+            pull_request_review(2, "CHANGES_REQUESTED", "2025-02-12T00:00:00Z"),
+        ],
+        issues: vec![
+            issue(1, "OPEN", "2025-02-01T00:00:00Z"),
+            issue(2, "CLOSED", "2025-02-02T00:00:00Z"),
+            issue(3, "CLOSED", "2025-02-03T00:00:00Z"),
+        ],
+        issue_comments: vec![issue_comment(
+            7,
+            true,
+            "2025-02-04T00:00:00Z",
+            "2025-02-04T00:00:00Z",
+        )],
+        discussions: vec![discussion(1, "2025-02-05T00:00:00Z", None)],
+        discussion_comments: vec![discussion_comment(2, true, "2025-02-06T00:00:00Z")],
+        repositories: vec![
+            repository("mine", None, "2025-01-10T00:00:00Z"),
+            repository("forked", Some("someone-else"), "2025-01-11T00:00:00Z"),
+        ],
+        restricted_contributions: vec![json!("2025-04-01T00:00:00Z")],
+        commits: vec![
+            commit("mine", 1, "2025-01-20T10:00:00Z"),
+            commit("mine", 2, "2025-01-20T11:00:00Z"),
+            // This is in a different window of days to the others:
+            commit("mine", 3, "2025-05-20T10:00:00Z"),
+            commit("forked", 4, "2025-02-01T10:00:00Z"),
+        ],
+        ..Contributions::default()
+    };
+    let github = FakeGitHub::start("octocat", contributions).await;
+
+    let out = github
+        .run_ok(&["--from", "2025-01-01", "--to", "2025-06-01"])
+        .await;
+
+    assert!(out.contains("- Opened 150 pull requests, of which 100 were merged."));
+    assert!(out.contains(
+        "- Left 3 reviews on 2 pull requests, of which 1 approved and 1 requested changes."
+    ));
+    assert!(out.contains("- Left 1 comments on 1 issues and pull requests."));
+    assert!(out.contains("- Started 1 discussions, of which 0 have been answered."));
+    assert!(
+        out.contains("- Left 1 comments on discussions, of which 1 were marked as the answer.")
+    );
+    assert!(out.contains("- Made 4 commits to 2 repositories."));
+    assert!(out.contains("| octocat/mine | 3 | 2 | 2025-01-20 | 2025-05-20 |"));
+    assert!(out.contains("| octocat/forked | 1 | 1 | 2025-02-01 | 2025-02-01 |"));
+    assert!(out.contains("- Opened 3 issues, of which 2 have been closed."));
+    assert!(out.contains("- Created 1 repositories (not counting forks)."));
+    assert!(
+        out.contains("- Plus 1 contributions in private repositories not visible with this token.")
+    );
+    assert!(!out.contains("Had no activity"));
+    assert!(out.contains("\"original_owner\": \"someone-else\""));
+    assert!(out.contains("\"state\": \"CHANGES_REQUESTED\""));
+    // Commit messages are only fetched when asked for.
+    assert!(!out.contains("Commit 1"));
+    assert!(github.requests_for("CommitHistory").await.is_empty());
+
+    // The first page of everything is fetched at once, and then 150 pull requests need a
+    // second page.
+    assert_eq!(github.requests_for("Contributions").await.len(), 1);
+    let pr_requests = github.requests_for("PullRequestContributions").await;
+    assert_eq!(pr_requests.len(), 1);
+    assert_eq!(pr_requests[0]["variables"]["cursor"], json!("100"));
+    assert!(
+        github
+            .requests_for("PullRequestReviewContributions")
+            .await
+            .is_empty()
+    );
+    assert!(github.requests_for("IssueContributions").await.is_empty());
+    assert!(github.requests_for("RepositoriesCreated").await.is_empty());
+
+    // The user is looked up from the token when not given.
+    for req in github.graphql_requests().await {
+        assert_eq!(req["variables"]["user"], json!("octocat"));
+    }
+}
+
+#[tokio::test]
+async fn lists_commit_messages_when_asked() {
+    let contributions = Contributions {
+        commits: (0..30)
+            .map(|n| commit("mine", n, "2025-03-01T12:00:00Z"))
+            .chain([commit("other", 30, "2025-03-02T12:00:00Z")])
+            .collect(),
+        ..Contributions::default()
+    };
+    let github = FakeGitHub::start("octocat", contributions).await;
+
+    let out = github
+        .run_ok(&[
+            "--commit-messages",
+            "--from",
+            "2025-01-01",
+            "--to",
+            "2025-06-01",
+        ])
+        .await;
+
+    assert!(out.contains("- Made 31 commits to 2 repositories."));
+    assert!(out.contains("\"message_headline\": \"Commit 29\""));
+    assert!(out.contains("\"repository\": \"octocat/other\""));
+
+    // The history of each repository is fetched over the whole range at once.
+    let requests = github.requests_for("CommitHistory").await;
+    assert_eq!(requests.len(), 2);
+    for req in requests {
+        assert_eq!(req["variables"]["owner"], json!("octocat"));
+        assert_eq!(
+            req["variables"]["from"],
+            json!("2025-01-01T00:00:00.000000000Z")
+        );
+    }
+}
+
+#[tokio::test]
+async fn counts_commits_across_windows_once() {
+    let contributions = Contributions {
+        // Either side of the boundary between the first two windows of days.
+        commits: vec![
+            commit("mine", 1, "2025-04-09T23:59:59Z"),
+            commit("mine", 2, "2025-04-10T00:00:00Z"),
+            commit("mine", 3, "2025-04-10T12:00:00Z"),
+        ],
+        ..Contributions::default()
+    };
+    let github = FakeGitHub::start("octocat", contributions).await;
+
+    let out = github
+        .run_ok(&["--from", "2025-01-01", "--to", "2025-06-01"])
+        .await;
+
+    assert!(out.contains("- Made 3 commits to 1 repositories."), "{out}");
+    assert!(out.contains("| octocat/mine | 3 | 2 | 2025-04-09 | 2025-04-10 |"));
+    let requests = github.requests_for("CommitContributions").await;
+    assert_eq!(requests.len(), 2);
+    assert_eq!(
+        requests[0]["variables"]["to"],
+        json!("2025-04-09T23:59:59.000000000Z")
+    );
+    assert_eq!(
+        requests[1]["variables"]["from"],
+        json!("2025-04-10T00:00:00.000000000Z")
+    );
+}
+
+#[tokio::test]
+async fn notes_when_commits_may_be_missing() {
+    let contributions = Contributions {
+        commits: (0..101)
+            .map(|n| commit(&format!("repo-{n}"), n, "2025-03-01T12:00:00Z"))
+            .collect(),
+        ..Contributions::default()
+    };
+    let github = FakeGitHub::start("octocat", contributions).await;
+
+    let output = github
+        .run(&["--from", "2025-01-01", "--to", "2025-06-01"])
+        .await;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(output.status.success(), "{stderr}");
+    assert!(stdout.contains("- Made 100 commits to 100 repositories."));
+    assert!(
+        stdout.contains("commits to some repositories may be missing"),
+        "{stdout}"
+    );
+    assert!(stderr.contains("Warning: commits"), "{stderr}");
+}
+
+#[tokio::test]
+async fn groups_comments_by_what_they_were_left_on() {
+    let contributions = Contributions {
+        issue_comments: [
+            issue_comment(1, false, "2025-02-01T00:00:00Z", "2025-02-01T00:00:00Z"),
+            issue_comment(1, false, "2025-02-02T00:00:00Z", "2025-04-01T00:00:00Z"),
+            issue_comment(2, true, "2025-03-01T00:00:00Z", "2025-03-01T00:00:00Z"),
+            // Edited during the window, but left before it.
+            issue_comment(3, false, "2024-12-01T00:00:00Z", "2025-01-05T00:00:00Z"),
+            // Left after the window.
+            issue_comment(4, false, "2025-07-01T00:00:00Z", "2025-07-01T00:00:00Z"),
+        ]
+        .into_iter()
+        // Plenty of comments from before the window, which we shouldn't page through.
+        .chain((0..200).map(|n| {
+            let at = format!("2024-06-{:02}T{:02}:00:00Z", n / 10 + 1, n % 10);
+            issue_comment(5, false, &at, &at)
+        }))
+        .collect(),
+        ..Contributions::default()
+    };
+    let github = FakeGitHub::start("octocat", contributions).await;
+
+    let out = github
+        .run_ok(&["--from", "2025-01-01", "--to", "2025-06-01"])
+        .await;
+
+    assert!(out.contains("- Left 3 comments on 2 issues and pull requests."));
+    assert!(out.contains("\"kind\": \"pull_request\""));
+    assert!(out.contains("\"url\": \"https://github.com/someone-else/repo/issues/1\""));
+    for n in [3, 4, 5] {
+        assert!(!out.contains(&format!("someone-else/repo/issues/{n}")));
+    }
+    assert_eq!(github.requests_for("IssueComments").await.len(), 1);
+}
+
+#[tokio::test]
+async fn summarizes_discussions() {
+    let contributions = Contributions {
+        discussions: [
+            discussion(1, "2025-02-01T00:00:00Z", Some("2025-02-03T00:00:00Z")),
+            discussion(2, "2025-03-01T00:00:00Z", None),
+            // Started after the window.
+            discussion(3, "2025-07-01T00:00:00Z", None),
+        ]
+        .into_iter()
+        // Plenty of discussions from before the window, which we shouldn't page through.
+        .chain((0..150).map(|n| discussion(100 + n, "2024-06-01T00:00:00Z", None)))
+        .collect(),
+        // Comments come in no particular order, so all of them are paged through.
+        discussion_comments: (0..120)
+            .map(|n| {
+                let at = format!("2024-06-{:02}T{:02}:00:00Z", n / 10 + 1, n % 10);
+                discussion_comment(100, false, &at)
+            })
+            .chain([
+                discussion_comment(1, true, "2025-02-02T00:00:00Z"),
+                discussion_comment(4, false, "2025-02-10T00:00:00Z"),
+                discussion_comment(4, false, "2025-07-10T00:00:00Z"),
+                // Left on a discussion that has since been deleted.
+                {
+                    let mut comment = discussion_comment(5, false, "2025-02-11T00:00:00Z");
+                    comment["discussion"] = json!(null);
+                    comment
+                },
+            ])
+            .collect(),
+        ..Contributions::default()
+    };
+    let github = FakeGitHub::start("octocat", contributions).await;
+
+    let out = github
+        .run_ok(&["--from", "2025-01-01", "--to", "2025-06-01"])
+        .await;
+
+    assert!(out.contains("- Started 2 discussions, of which 1 have been answered."));
+    assert!(
+        out.contains("- Left 2 comments on discussions, of which 1 were marked as the answer.")
+    );
+    assert!(out.contains("\"category\": \"Q&A\""));
+    assert!(out.contains("\"discussion_title\": \"Discussion 4\""));
+    assert!(out.contains("which were accepted as the answer"));
+    assert!(!out.contains("Discussion 3"));
+    assert!(!out.contains("Discussion 100"));
+    assert!(!out.contains("were skipped"), "{out}");
+    assert_eq!(github.requests_for("RepositoryDiscussions").await.len(), 1);
+    // Two pages of all of the comments, and one of the answers.
+    let requests = github.requests_for("DiscussionComments").await;
+    assert_eq!(requests.len(), 3);
+    let answers = requests
+        .iter()
+        .filter(|req| req["variables"]["onlyAnswers"] == json!(true));
+    assert_eq!(answers.count(), 1);
+}
+
+#[tokio::test]
+async fn splits_ranges_longer_than_a_year() {
+    let contributions = Contributions {
+        pull_requests: vec![
+            pull_request(1, "MERGED", "2022-06-01T00:00:00Z"),
+            pull_request(2, "MERGED", "2023-06-01T00:00:00Z"),
+            pull_request(3, "MERGED", "2024-06-01T00:00:00Z"),
+            // This lands exactly on a boundary between two windows:
+            pull_request(4, "MERGED", "2023-01-01T00:00:00Z"),
+        ],
+        restricted_contributions: vec![
+            json!("2022-03-01T00:00:00Z"),
+            json!("2024-03-01T00:00:00Z"),
+        ],
+        new_user: true,
+        ..Contributions::default()
+    };
+    let github = FakeGitHub::start("octocat", contributions).await;
+
+    let out = github
+        .run_ok(&[
+            "--user",
+            "octocat",
+            "--from",
+            "2022-01-01",
+            "--to",
+            "2024-12-31",
+        ])
+        .await;
+
+    assert!(out.contains("- Opened 4 pull requests, of which 4 were merged."));
+    // Counts of what the token can't see are added up over every window.
+    assert!(
+        out.contains("- Plus 2 contributions in private repositories not visible with this token.")
+    );
+    assert!(out.contains("- Had no activity on GitHub before this period."));
+
+    // 2024 is a leap year, so three years need four windows of at most 365 days.
+    let requests = github.requests_for("Contributions").await;
+    assert_eq!(requests.len(), 4);
+    for req in requests {
+        let from = common::parse_date(&req["variables"]["from"]);
+        let to = common::parse_date(&req["variables"]["to"]);
+        assert!(to - from <= time::Duration::days(365));
+    }
+}
+
+#[tokio::test]
+async fn retries_transient_failures() {
+    let contributions = Contributions {
+        issues: vec![issue(1, "OPEN", "2025-02-01T00:00:00Z")],
+        ..Contributions::default()
+    };
+    let github = FakeGitHub::start("octocat", contributions).await;
+    github
+        .fail_next_queries(
+            "Contributions",
+            1,
+            ResponseTemplate::new(502).set_body_string("Bad gateway"),
+        )
+        .await;
+
+    let out = github
+        .run_ok(&["--from", "2025-01-01", "--to", "2025-06-01"])
+        .await;
+
+    assert!(out.contains("- Opened 1 issues, of which 0 have been closed."));
+}
+
+#[tokio::test]
+async fn waits_for_the_rate_limit_to_reset() {
+    let contributions = Contributions {
+        pull_requests: (0..150)
+            .map(|n| pull_request(n, "MERGED", "2025-03-01T12:00:00Z"))
+            .collect(),
+        ..Contributions::default()
+    };
+    let github = FakeGitHub::start("octocat", contributions).await;
+    // The second page of pull requests can only be asked for after this.
+    github.exhaust_rate_limit("Contributions", Duration::from_secs(1));
+
+    let start = Instant::now();
+    let output = github
+        .run(&["--from", "2025-01-01", "--to", "2025-06-01"])
+        .await;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(output.status.success(), "{stderr}");
+    assert!(start.elapsed() >= Duration::from_secs(1));
+    assert!(stdout.contains("- Opened 150 pull requests, of which 150 were merged."));
+    // Only one of the requests waiting says so.
+    assert_eq!(
+        stderr.matches("GitHub rate limit exhausted").count(),
+        1,
+        "{stderr}"
+    );
+}
+
+#[tokio::test]
+async fn retries_once_the_rate_limit_resets() {
+    let contributions = Contributions {
+        issues: vec![issue(1, "OPEN", "2025-02-01T00:00:00Z")],
+        ..Contributions::default()
+    };
+    let github = FakeGitHub::start("octocat", contributions).await;
+    github
+        .fail_next_queries(
+            "Contributions",
+            1,
+            ResponseTemplate::new(403)
+                .insert_header("X-RateLimit-Remaining", "0")
+                .insert_header(
+                    "X-RateLimit-Reset",
+                    common::rate_limit_reset(Duration::from_secs(1)).to_string(),
+                )
+                .set_body_json(json!({ "message": "API rate limit exceeded" })),
+        )
+        .await;
+
+    let output = github
+        .run(&[
+            "--from",
+            "2025-01-01",
+            "--to",
+            "2025-06-01",
+            "--max-attempts",
+            "1",
+        ])
+        .await;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    // This doesn't count as a failed attempt.
+    assert!(output.status.success(), "{stderr}");
+    assert!(stdout.contains("- Opened 1 issues, of which 0 have been closed."));
+    assert!(stderr.contains("GitHub rate limit exhausted"), "{stderr}");
+    assert_eq!(github.requests_for("Contributions").await.len(), 2);
+}
+
+#[tokio::test]
+async fn retries_after_secondary_rate_limits() {
+    let contributions = Contributions {
+        issues: vec![issue(1, "OPEN", "2025-02-01T00:00:00Z")],
+        ..Contributions::default()
+    };
+    let github = FakeGitHub::start("octocat", contributions).await;
+    github
+        .fail_next_queries(
+            "Contributions",
+            1,
+            ResponseTemplate::new(403)
+                .insert_header("Retry-After", "1")
+                .set_body_json(json!({
+                    "message": "You have exceeded a secondary rate limit. Please wait a few minutes before you try again."
+                })),
+        )
+        .await;
+
+    let start = Instant::now();
+    let output = github
+        .run(&["--from", "2025-01-01", "--to", "2025-06-01"])
+        .await;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(output.status.success(), "{stderr}");
+    assert!(stdout.contains("- Opened 1 issues, of which 0 have been closed."));
+    // We wait for as long as GitHub asked, rather than backing off from a second.
+    assert!(stderr.contains("Secondary rate limit hit"), "{stderr}");
+    assert!(start.elapsed() >= Duration::from_secs(1));
+    assert_eq!(github.requests_for("Contributions").await.len(), 2);
+}
+
+#[tokio::test]
+async fn gives_up_on_bad_responses() {
+    let github = FakeGitHub::start("octocat", Contributions::default()).await;
+    github
+        .fail_next_queries(
+            "Contributions",
+            1,
+            ResponseTemplate::new(401).set_body_string("Bad credentials"),
+        )
+        .await;
+
+    let output = github
+        .run(&["--from", "2025-01-01", "--to", "2025-06-01"])
+        .await;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(!output.status.success());
+    assert!(stderr.contains("401 response: Bad credentials"), "{stderr}");
+    assert_eq!(github.requests_for("Contributions").await.len(), 1);
+}
+
+#[tokio::test]
+async fn reports_query_errors() {
+    let github = FakeGitHub::start("octocat", Contributions::default()).await;
+    github
+        .fail_next_queries(
+            "Contributions",
+            1,
+            ResponseTemplate::new(200).set_body_json(json!({
+                "data": { "user": null },
+                "errors": [{
+                    "type": "NOT_FOUND",
+                    "path": ["user"],
+                    "locations": [{ "line": 2, "column": 5 }],
+                    "message": "Could not resolve to a User with the login of 'nobody'."
+                }]
+            })),
+        )
+        .await;
+
+    let output = github
+        .run(&[
+            "--user",
+            "nobody",
+            "--from",
+            "2025-01-01",
+            "--to",
+            "2025-06-01",
+        ])
+        .await;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(!output.status.success());
+    assert!(
+        stderr.contains("GitHub user 'nobody' not found"),
+        "{stderr}"
+    );
+    // Not found errors aren't worth retrying.
+    assert_eq!(github.requests_for("Contributions").await.len(), 1);
+}
+
+#[tokio::test]
+async fn shrinks_page_size_on_timeout() {
+    let contributions = Contributions {
+        pull_requests: (0..30)
+            .map(|n| pull_request(n, "MERGED", "2025-03-01T12:00:00Z"))
+            .collect(),
+        ..Contributions::default()
+    };
+    let github = FakeGitHub::start("octocat", contributions).await;
+    github
+        .fail_next_queries(
+            "Contributions",
+            1,
+            ResponseTemplate::new(200).set_body_json(json!({
+                "data": null,
+                "errors": [{
+                    "message": "Something went wrong while executing your query. This may be the result of a timeout, or it could be a GitHub bug."
+                }]
+            })),
+        )
+        .await;
+
+    let out = github
+        .run_ok(&["--from", "2025-01-01", "--to", "2025-06-01"])
+        .await;
+
+    assert!(out.contains("- Opened 30 pull requests, of which 30 were merged."));
+
+    let page_sizes: Vec<_> = github
+        .requests_for("Contributions")
+        .await
+        .iter()
+        .map(|req| req["variables"]["first"].as_u64().unwrap())
+        .collect();
+    assert_eq!(page_sizes, vec![100, 50]);
+}
+
+#[tokio::test]
+async fn replays_recorded_runs() {
+    let contributions = Contributions {
+        pull_requests: vec![pull_request(1, "MERGED", "2025-03-01T12:00:00Z")],
+        pull_request_reviews: vec![pull_request_review(1, "APPROVED", "2025-03-02T12:00:00Z")],
+        issues: vec![issue(1, "OPEN", "2025-02-01T00:00:00Z")],
+        issue_comments: vec![issue_comment(
+            1,
+            false,
+            "2025-02-02T00:00:00Z",
+            "2025-02-02T00:00:00Z",
+        )],
+        discussions: vec![discussion(1, "2025-02-03T00:00:00Z", None)],
+        discussion_comments: vec![discussion_comment(2, false, "2025-02-04T00:00:00Z")],
+        repositories: vec![repository("mine", None, "2025-01-10T00:00:00Z")],
+        commits: vec![commit("mine", 1, "2025-01-10T00:00:00Z")],
+        ..Contributions::default()
+    };
+    let github = FakeGitHub::start("octocat", contributions).await;
+    let dir = TempDir::new("replay");
+    let dir_str = dir.path().to_str().unwrap();
+
+    let recorded = github.run_ok(&["--from", "30d", "--record", dir_str]).await;
+
+    // Replaying shouldn't touch the API at all.
+    let requests_made = github.server.received_requests().await.unwrap().len();
+    let replayed = github.run_ok(&["--from", "30d", "--replay", dir_str]).await;
+
+    assert_eq!(recorded, replayed);
+    assert_eq!(
+        github.server.received_requests().await.unwrap().len(),
+        requests_made
+    );
+
+    // Asking for something that wasn't recorded fails.
+    let output = github.run(&["--from", "60d", "--replay", dir_str]).await;
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No recorded response"));
+}
+
+#[tokio::test]
+async fn caches_responses() {
+    let contributions = Contributions {
+        pull_requests: vec![pull_request(1, "MERGED", "2025-03-01T12:00:00Z")],
+        ..Contributions::default()
+    };
+    let github = FakeGitHub::start("octocat", contributions).await;
+    let args = [
+        "--user",
+        "octocat",
+        "--from",
+        "2025-01-01",
+        "--to",
+        "2025-06-01",
+    ];
+
+    // One query for the first pages of everything, two for commits, and one each for
+    // comments, discussions, comments on discussions and answers to discussions.
+    let first = github.run_ok(&args).await;
+    assert_eq!(github.graphql_requests().await.len(), 7);
+
+    // The second run is served entirely from the cache.
+    let second = github.run_ok(&args).await;
+    assert_eq!(first, second);
+    assert_eq!(github.graphql_requests().await.len(), 7);
+
+    // Another token may be able to see more or less, so it doesn't share responses.
+    let output = github
+        .run_without_token(&[&["--gh-token", "other-token"], &args[..]].concat(), None)
+        .await;
+    assert!(output.status.success());
+    assert_eq!(github.graphql_requests().await.len(), 14);
+
+    // Unless we ask for fresh data, or not to use the cache.
+    github.run_ok(&[&args[..], &["--refresh"]].concat()).await;
+    assert_eq!(github.graphql_requests().await.len(), 21);
+    github.run_ok(&[&args[..], &["--no-cache"]].concat()).await;
+    assert_eq!(github.graphql_requests().await.len(), 28);
+
+    // Comments and discussions aren't asked for by date, so they're never historical.
+    let stats = github.run_subcommand(&["cache", "stats"]).await;
+    assert!(stats.contains("Cached responses: 14"), "{stats}");
+    assert!(stats.contains("of which historical: 6"), "{stats}");
+
+    let cleared = github.run_subcommand(&["cache", "clear"]).await;
+    assert!(cleared.contains("Removed 14 cached responses"), "{cleared}");
+    github.run_ok(&args).await;
+    assert_eq!(github.graphql_requests().await.len(), 35);
+}
+
+#[tokio::test]
+async fn caches_responses_up_to_now() {
+    let github = FakeGitHub::start("octocat", Contributions::default()).await;
+    let args = ["--user", "octocat", "--from", "7d", "--cache-ttl", "1d"];
+
+    github.run_ok(&args).await;
+    let requests = github.requests_for("Contributions").await;
+    assert_eq!(requests.len(), 1);
+    // Now is rounded down to the cache TTL, so runs within it ask for the same window.
+    let to = requests[0]["variables"]["to"].as_str().unwrap().to_owned();
+    assert!(to.ends_with("T00:00:00.000000000Z"), "{to}");
+
+    let before = github.graphql_requests().await.len();
+    github.run_ok(&args).await;
+    assert_eq!(github.graphql_requests().await.len(), before);
+
+    // Without the cache there's no need to round, so we ask about up to now.
+    github.run_ok(&[&args[..], &["--no-cache"]].concat()).await;
+    let requests = github.requests_for("Contributions").await;
+    assert_eq!(requests.len(), 2);
+    assert_ne!(requests[1]["variables"]["to"], json!(to));
+}
+
+#[tokio::test]
+async fn reports_partial_responses() {
+    let github = FakeGitHub::start("octocat", Contributions::default()).await;
+    github
+        .fail_next_queries(
+            "Contributions",
+            1,
+            ResponseTemplate::new(200).set_body_json(json!({
+                "data": {
+                    "rate_limit": { "cost": 1 },
+                    "user": {
+                        "contributions_collection": {
+                            "restricted_contributions_count": 0,
+                            "has_any_restricted_contributions": false,
+                            "has_activity_in_the_past": true,
+                            "pull_request_contributions": {
+                                "page_info": { "end_cursor": "2", "has_next_page": false },
+                                "nodes": [
+                                    { "pull_request": pull_request(1, "MERGED", "2025-03-01T12:00:00Z") },
+                                    null
+                                ]
+                            },
+                            "pull_request_review_contributions": {
+                                "page_info": { "end_cursor": null, "has_next_page": false },
+                                "nodes": []
+                            },
+                            "issue_contributions": {
+                                "page_info": { "end_cursor": null, "has_next_page": false },
+                                "nodes": []
+                            },
+                            "repository_contributions": {
+                                "page_info": { "end_cursor": null, "has_next_page": false },
+                                "nodes": []
+                            }
+                        }
+                    }
+                },
+                "errors": [{
+                    "path": ["user", "contributions_collection", "pull_request_contributions", "nodes", 1],
+                    "message": "Resource not accessible by integration"
+                }]
+            })),
+        )
+        .await;
+
+    let output = github
+        .run(&["--from", "2025-01-01", "--to", "2025-06-01"])
+        .await;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(output.status.success(), "{stderr}");
+    assert!(stdout.contains("- Opened 1 pull requests, of which 1 were merged."));
+    assert!(
+        stdout.contains("- 1 pull requests were skipped."),
+        "{stdout}"
+    );
+    assert!(
+        stdout.contains(
+            "Resource not accessible by integration \
+            (at user.contributions_collection.pull_request_contributions.nodes.1)"
+        ),
+        "{stdout}"
+    );
+    assert!(stderr.contains("Warning: query Contributions"), "{stderr}");
+}
+
+#[tokio::test]
+async fn explains_rejected_tokens() {
+    let github = FakeGitHub::start("octocat", Contributions::default()).await;
+    github
+        .respond_to_user(
+            ResponseTemplate::new(401).set_body_json(json!({ "message": "Bad credentials" })),
+        )
+        .await;
+
+    let output = github
+        .run(&["--from", "2025-01-01", "--to", "2025-06-01"])
+        .await;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(!output.status.success());
+    assert!(
+        stderr.contains("GitHub rejected the token (401: Bad credentials)"),
+        "{stderr}"
+    );
+    assert!(github.graphql_requests().await.is_empty());
+}
+
+#[tokio::test]
+async fn warns_about_token_limitations() {
+    let github = FakeGitHub::start("octocat", Contributions::default()).await;
+    github
+        .respond_to_user(
+            ResponseTemplate::new(200)
+                .insert_header("X-OAuth-Scopes", "read:org")
+                .set_body_json(json!({ "login": "octocat" })),
+        )
+        .await;
+    // GitHub only says which organizations were left out when queries touch them.
+    github.add_graphql_header("X-GitHub-SSO", "partial-results; organizations=21955855");
+
+    let output = github
+        .run(&["--from", "2025-01-01", "--to", "2025-06-01"])
+        .await;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(output.status.success(), "{stderr}");
+    assert!(stderr.contains("doesn't have the 'repo' scope"), "{stderr}");
+    assert_eq!(stderr.matches("(IDs: 21955855)").count(), 1, "{stderr}");
+}
+
+#[tokio::test]
+async fn points_to_sso_authorization() {
+    let url = "https://github.com/orgs/acme/sso?authorization_request=abc";
+    let github = FakeGitHub::start("octocat", Contributions::default()).await;
+    github
+        .fail_next_queries(
+            "Contributions",
+            1,
+            ResponseTemplate::new(403)
+                .insert_header("X-GitHub-SSO", format!("required; url={url}").as_str())
+                .set_body_json(
+                    json!({ "message": "Resource protected by organization SAML enforcement." }),
+                ),
+        )
+        .await;
+
+    let output = github
+        .run(&["--from", "2025-01-01", "--to", "2025-06-01"])
+        .await;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(!output.status.success());
+    assert!(stderr.contains(url), "{stderr}");
+}
+
+#[tokio::test]
+async fn finds_tokens_from_other_sources() {
+    let github = FakeGitHub::start("octocat", Contributions::default()).await;
+    let args = ["--from", "2025-01-01", "--to", "2025-06-01"];
+
+    let output = github.run_without_token(&args, None).await;
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No GitHub token found"));
+
+    let token_dir = TempDir::new("token");
+    std::fs::create_dir_all(token_dir.path()).unwrap();
+    let token_file = token_dir.path().join("token");
+    std::fs::write(&token_file, "file-token\n").unwrap();
+    let file_args = ["--gh-token-file", token_file.to_str().unwrap()];
+    let output = github
+        .run_without_token(&[&args[..], &file_args].concat(), None)
+        .await;
+    assert!(output.status.success());
+
+    let stdin_args = ["--gh-token-stdin"];
+    let output = github
+        .run_without_token(&[&args[..], &stdin_args].concat(), Some("stdin-token\n"))
+        .await;
+    assert!(output.status.success());
+
+    // The fake server is on 127.0.0.1, so that's the host the GitHub CLI would log in to.
+    let host = github.server.address().to_string();
+    std::fs::create_dir_all(github.gh_config_dir.path()).unwrap();
+    std::fs::write(
+        github.gh_config_dir.path().join("hosts.yml"),
+        format!("{host}:\n    user: octocat\n    oauth_token: gh-cli-token\n"),
+    )
+    .unwrap();
+    let output = github.run_without_token(&args, None).await;
+    assert!(output.status.success());
+
+    let mut tokens = github.tokens_used().await;
+    tokens.dedup();
+    assert_eq!(tokens, vec!["file-token", "stdin-token", "gh-cli-token"]);
+}
+
+#[tokio::test]
+async fn authenticates_as_a_github_app() {
+    let contributions = Contributions {
+        issues: vec![issue(1, "OPEN", "2025-02-01T00:00:00Z")],
+        ..Contributions::default()
+    };
+    let github = FakeGitHub::start("octocat", contributions).await;
+    github
+        .serve_installation_tokens("1234", 42, time::Duration::hours(1))
+        .await;
+    let key = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/app-private-key.pem"
+    );
+    let app_args = [
+        "--app-id",
+        "1234",
+        "--app-private-key",
+        key,
+        "--app-installation-id",
+        "42",
+        "--from",
+        "2025-01-01",
+        "--to",
+        "2025-06-01",
+    ];
+
+    // Apps don't have a user of their own.
+    let output = github.run_without_token(&app_args, None).await;
+    assert!(!output.status.success());
+
+    let out = String::from_utf8(
+        github
+            .run_without_token(&[&app_args[..], &["--user", "octocat"]].concat(), None)
+            .await
+            .stdout,
+    )
+    .unwrap();
+    assert!(out.contains("- Opened 1 issues, of which 0 have been closed."));
+
+    // One token is used for everything, and the user isn't looked up.
+    assert_eq!(github.installation_tokens_issued().await, 1);
+    let tokens = github.tokens_used().await;
+    assert_eq!(tokens.len(), 8);
+    assert!(tokens[1..].iter().all(|t| t == "installation-token-0"));
+}
+
+#[tokio::test]
+async fn refreshes_expiring_installation_tokens() {
+    let github = FakeGitHub::start("octocat", Contributions::default()).await;
+    // Tokens which are about to expire are replaced before each request.
+    github
+        .serve_installation_tokens("1234", 42, time::Duration::minutes(2))
+        .await;
+    let key = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/app-private-key.pem"
+    );
+
+    let output = github
+        .run_without_token(
+            &[
+                "--app-id",
+                "1234",
+                "--app-private-key",
+                key,
+                "--app-installation-id",
+                "42",
+                "--user",
+                "octocat",
+                "--from",
+                "2023-01-01",
+                "--to",
+                "2025-06-01",
+            ],
+            None,
+        )
+        .await;
+    assert!(output.status.success());
+
+    let requests = github.graphql_requests().await.len();
+    assert!(requests > 1);
+    assert_eq!(github.installation_tokens_issued().await, requests);
+    let tokens = github.tokens_used().await;
+    assert!(tokens.contains(&format!("installation-token-{}", requests - 1)));
+}
+
+#[tokio::test]
+async fn fetches_concurrently_up_to_a_limit() {
+    let github = FakeGitHub::start("octocat", Contributions::default()).await;
+    // Long enough that requests sent together are answered together.
+    github.delay_graphql(Duration::from_millis(200));
+    // Two yearly windows, each with every category fetched together, six windows of days
+    // for commits, and one page each of comments, discussions, discussion comments and
+    // answers to discussions.
+    let args = [
+        "--user",
+        "octocat",
+        "--no-cache",
+        "--from",
+        "2024-01-01",
+        "--to",
+        "2025-06-01",
+    ];
+
+    github
+        .run_ok(&[&args[..], &["--concurrency", "1"]].concat())
+        .await;
+    assert_eq!(github.take_peak_graphql_in_flight(), 1);
+
+    github
+        .run_ok(&[&args[..], &["--concurrency", "4"]].concat())
+        .await;
+    let peak = github.take_peak_graphql_in_flight();
+    assert!(
+        peak > 1 && peak <= 4,
+        "{peak} requests were in flight at once"
+    );
+    assert_eq!(github.graphql_requests().await.len(), 24);
+}
+
+#[tokio::test]
+async fn streams_json_lines() {
+    let contributions = Contributions {
+        pull_requests: (0..120)
+            .map(|n| pull_request(n, "MERGED", "2025-03-01T12:00:00Z"))
+            .collect(),
+        pull_request_reviews: vec![pull_request_review(1, "APPROVED", "2025-03-02T12:00:00Z")],
+        issues: vec![issue(1, "OPEN", "2025-02-01T00:00:00Z")],
+        issue_comments: vec![
+            issue_comment(1, false, "2025-02-02T00:00:00Z", "2025-02-02T00:00:00Z"),
+            issue_comment(1, false, "2025-02-03T00:00:00Z", "2025-02-03T00:00:00Z"),
+            issue_comment(2, true, "2025-02-04T00:00:00Z", "2025-02-04T00:00:00Z"),
+        ],
+        discussions: vec![discussion(1, "2025-02-05T00:00:00Z", None)],
+        discussion_comments: vec![discussion_comment(2, true, "2025-02-06T00:00:00Z")],
+        // This lands exactly on a boundary between two windows, but is only streamed once.
+        repositories: vec![repository("mine", None, "2025-01-01T00:00:00Z")],
+        commits: vec![commit("mine", 1, "2025-01-10T00:00:00Z")],
+        ..Contributions::default()
+    };
+    let github = FakeGitHub::start("octocat", contributions).await;
+
+    let out = github
+        .run_ok(&["--stream", "--from", "2024-01-01", "--to", "2025-06-01"])
+        .await;
+
+    let lines: Vec<serde_json::Value> = out
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("each line should be JSON"))
+        .collect();
+    let count = |item_type: &str| lines.iter().filter(|l| l["type"] == item_type).count();
+    assert_eq!(count("pull_request"), 120);
+    assert_eq!(count("pull_request_review"), 1);
+    assert_eq!(count("issue"), 1);
+    assert_eq!(count("issue_comments"), 2);
+    assert_eq!(count("discussion"), 1);
+    assert_eq!(count("discussion_comment"), 1);
+    assert_eq!(count("discussion_answer"), 1);
+    assert_eq!(count("repository"), 1);
+    assert_eq!(count("repository_commits"), 1);
+    assert_eq!(lines.len(), 129);
+}